@@ -0,0 +1,225 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{bail, Context, Error};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::pinout::{Pin, FIELDS};
+
+///Unit used for pins no rule matches, unless the config names another one
+const DEFAULT_UNIT: &str = "Other";
+
+///`--config` file contents
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    ///Unit for pins that match no rule
+    pub default_unit: Option<String>,
+    ///Grouping rules, the first matching rule wins
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<RuleConfig>,
+}
+
+///One `[[rule]]` table
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    ///Regex matched against the pin name
+    pub pin_name: Option<String>,
+    ///Regexes matched against other columns, keyed by header name
+    #[serde(default)]
+    pub columns: BTreeMap<String, String>,
+    ///Target unit. `$1`/`${name}` expand `pin_name` captures and `{Header}`
+    ///expands to the column value, e.g. `"Bank {Bank}"`
+    pub unit: String,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("failed to parse {}", path.display()))
+    }
+}
+
+///A compiled grouping rule
+#[derive(Debug)]
+struct GroupRule {
+    pin_name: Option<Regex>,
+    columns: Vec<(String, Regex)>,
+    unit: String,
+}
+
+impl GroupRule {
+    ///Unit key for the pin, or None when the rule does not apply
+    fn apply(&self, pin: &Pin) -> Option<String> {
+        for (header, re) in &self.columns {
+            if !re.is_match(&pin.field(header)?) {
+                return None;
+            }
+        }
+
+        let mut unit = String::new();
+        match &self.pin_name {
+            Some(re) => re.captures(&pin.name)?.expand(&self.unit, &mut unit),
+            None => unit.push_str(&self.unit),
+        }
+        Some(expand_columns(&unit, pin))
+    }
+}
+
+///Replace `{Header}` placeholders with the column values of a pin
+fn expand_columns(template: &str, pin: &Pin) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let header = &rest[start + 1..start + len];
+        match pin.field(header) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..=start + len]),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+///Ordered grouping rules loaded from a config file
+#[derive(Debug)]
+pub struct GroupRules {
+    rules: Vec<GroupRule>,
+    default_unit: String,
+}
+
+impl GroupRules {
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let mut rules = Vec::with_capacity(config.rules.len());
+        for (i, rule) in config.rules.iter().enumerate() {
+            let context = || format!("rule #{} ({:?})", i + 1, rule.unit);
+            let pin_name = rule
+                .pin_name
+                .as_deref()
+                .map(Regex::new)
+                .transpose()
+                .with_context(context)?;
+            let columns = rule
+                .columns
+                .iter()
+                .map(|(header, re)| {
+                    // 未知的列名会让规则永远不匹配
+                    if !FIELDS.contains(&header.as_str()) {
+                        bail!(
+                            "unknown column {:?}, known columns: {}",
+                            header,
+                            FIELDS.join(", ")
+                        );
+                    }
+                    Ok((header.clone(), Regex::new(re)?))
+                })
+                .collect::<Result<_, Error>>()
+                .with_context(context)?;
+            rules.push(GroupRule {
+                pin_name,
+                columns,
+                unit: rule.unit.clone(),
+            });
+        }
+
+        Ok(GroupRules {
+            rules,
+            default_unit: config
+                .default_unit
+                .clone()
+                .unwrap_or(DEFAULT_UNIT.to_string()),
+        })
+    }
+
+    ///Unit key of the first matching rule
+    pub fn unit_for(&self, pin: &Pin) -> String {
+        self.rules
+            .iter()
+            .find_map(|rule| rule.apply(pin))
+            .unwrap_or_else(|| self.default_unit.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinout::IoType;
+
+    fn rules(text: &str) -> GroupRules {
+        GroupRules::new(&toml::from_str(text).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = rules(
+            r#"
+            [[rule]]
+            pin_name = '^VCCO_'
+            unit = "Power"
+
+            [[rule]]
+            pin_name = '^VCC'
+            unit = "Core"
+            "#,
+        );
+        let pin = Pin::test("A1", "VCCO_44", Some(44), IoType::Na);
+        assert_eq!(rules.unit_for(&pin), "Power");
+        let pin = Pin::test("A2", "VCCINT", None, IoType::Na);
+        assert_eq!(rules.unit_for(&pin), "Core");
+    }
+
+    #[test]
+    fn expands_captures_and_columns() {
+        let rules = rules(
+            r#"
+            [[rule]]
+            pin_name = '^MGT\w*?(?P<dir>[RT]X)[PN]\d+_(\d+)$'
+            unit = "Quad $2 ${dir}"
+
+            [[rule]]
+            columns = { "I/O Type" = "^H[PD]$" }
+            unit = "Bank {Bank} ({I/O Type}) {Unknown}"
+            "#,
+        );
+        let pin = Pin::test("R4", "MGTHRXP0_128", Some(128), IoType::Gth);
+        assert_eq!(rules.unit_for(&pin), "Quad 128 RX");
+        let pin = Pin::test("AN14", "IO_L1P_AD11P_44", Some(44), IoType::Hd);
+        assert_eq!(rules.unit_for(&pin), "Bank 44 (HD) {Unknown}");
+    }
+
+    #[test]
+    fn unmatched_pins_go_to_the_default_unit() {
+        let pin = Pin::test("A1", "GND", None, IoType::Na);
+        assert_eq!(rules("").unit_for(&pin), "Other");
+        assert_eq!(rules("default_unit = \"Misc\"").unit_for(&pin), "Misc");
+    }
+
+    #[test]
+    fn reports_bad_regex() {
+        let config = toml::from_str("[[rule]]\npin_name = '^IO_('\nunit = \"IO\"").unwrap();
+        let error = GroupRules::new(&config).unwrap_err();
+        assert!(format!("{:#}", error).starts_with("rule #1 (\"IO\")"));
+    }
+
+    #[test]
+    fn reports_unknown_column() {
+        let config = toml::from_str(
+            "[[rule]]\npin_name = '^IO_'\nunit = \"IO\"\n\n\
+             [[rule]]\ncolumns = { \"IO Type\" = '^HP$' }\nunit = \"HP\"",
+        )
+        .unwrap();
+        let error = format!("{:#}", GroupRules::new(&config).unwrap_err());
+        assert!(
+            error.starts_with("rule #2 (\"HP\"): unknown column \"IO Type\""),
+            "{}",
+            error
+        );
+    }
+}
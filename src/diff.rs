@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use crate::{
+    pinout::{Pin, PinoutTable},
+    sort::ball_cmp,
+};
+
+///Columns compared between two pinout files
+const COMPARED: [&str; 4] = ["Pin Name", "Bank", "I/O Type", "Memory Byte Group"];
+
+///A column whose value differs for the same ball
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldChange {
+    pub header: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+///Differences between the pinouts of two pin-compatible devices
+#[derive(Clone, Debug, Default)]
+pub struct PinoutDiff {
+    ///Balls in both files with at least one differing column
+    pub changed: Vec<(Pin, Vec<FieldChange>)>,
+    pub only_old: Vec<Pin>,
+    pub only_new: Vec<Pin>,
+    ///Balls with the same name and I/O type in both files, safe to use on
+    ///either device
+    pub shared: Vec<Pin>,
+}
+
+impl PinoutDiff {
+    ///Compare two pinouts ball by ball, results are in ball order
+    pub fn new(old: &PinoutTable, new: &PinoutTable) -> Self {
+        let new_pins: HashMap<&str, &Pin> =
+            new.pins.iter().map(|p| (p.number.as_str(), p)).collect();
+        let old_balls: HashMap<&str, &Pin> =
+            old.pins.iter().map(|p| (p.number.as_str(), p)).collect();
+
+        let mut diff = PinoutDiff::default();
+        for pin in &old.pins {
+            let Some(other) = new_pins.get(pin.number.as_str()) else {
+                diff.only_old.push(pin.clone());
+                continue;
+            };
+            let changes = field_changes(pin, other);
+            // 名称和 I/O 类型都相同才算两种器件共有的功能
+            if pin.name == other.name && pin.io_type == other.io_type {
+                diff.shared.push(pin.clone());
+            }
+            if !changes.is_empty() {
+                diff.changed.push((pin.clone(), changes));
+            }
+        }
+        diff.only_new = new
+            .pins
+            .iter()
+            .filter(|p| !old_balls.contains_key(p.number.as_str()))
+            .cloned()
+            .collect();
+
+        diff.changed
+            .sort_by(|a, b| ball_cmp(&a.0.number, &b.0.number));
+        for pins in [&mut diff.only_old, &mut diff.only_new, &mut diff.shared] {
+            pins.sort_by(|a, b| ball_cmp(&a.number, &b.number));
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.only_old.is_empty() && self.only_new.is_empty()
+    }
+
+    ///Render the differences as text, one ball per line
+    pub fn report(&self, old_name: &str, new_name: &str) -> String {
+        let mut text = String::new();
+        text.push_str(&format!("--- {}\n+++ {}\n", old_name, new_name));
+        for (pin, changes) in &self.changed {
+            let changes: Vec<String> = changes
+                .iter()
+                .map(|c| format!("{}: {} -> {}", c.header, c.old, c.new))
+                .collect();
+            text.push_str(&format!("~ {:<6} {}\n", pin.number, changes.join(", ")));
+        }
+        for pin in &self.only_old {
+            text.push_str(&format!("- {:<6} {}\n", pin.number, pin.name));
+        }
+        for pin in &self.only_new {
+            text.push_str(&format!("+ {:<6} {}\n", pin.number, pin.name));
+        }
+        text
+    }
+}
+
+fn field_changes(old: &Pin, new: &Pin) -> Vec<FieldChange> {
+    COMPARED
+        .iter()
+        .filter_map(|&header| {
+            let a = old.field(header)?;
+            let b = new.field(header)?;
+            (a != b).then_some(FieldChange {
+                header,
+                old: a,
+                new: b,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IoType;
+
+    fn table(pins: &[(&str, &str, IoType)]) -> PinoutTable {
+        PinoutTable::test(
+            pins.iter()
+                .map(|(number, name, io_type)| Pin::test(number, name, None, io_type.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn finds_changed_and_missing_balls() {
+        let old = table(&[
+            ("A1", "GND", IoType::Na),
+            ("A2", "IO_L1P_64", IoType::Hp),
+            ("A3", "MGTHRXP0_128", IoType::Gth),
+        ]);
+        let new = table(&[
+            ("A1", "GND", IoType::Na),
+            ("A2", "IO_L1P_64", IoType::Hd),
+            ("B1", "VCCINT", IoType::Na),
+        ]);
+        let diff = PinoutDiff::new(&old, &new);
+
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].1[0].header, "I/O Type");
+        assert_eq!(diff.only_old[0].number, "A3");
+        assert_eq!(diff.only_new[0].number, "B1");
+        let shared: Vec<&str> = diff.shared.iter().map(|p| p.number.as_str()).collect();
+        assert_eq!(shared, vec!["A1"]);
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    io,
+    path::{Path, PathBuf},
+};
+
+///Why a pinout file, or one of its lines, could not be read
+#[derive(Debug)]
+pub enum PinoutError {
+    ///The file could not be opened or read
+    Io {
+        file: PathBuf,
+        ///1-based line being read, None when opening failed
+        line: Option<usize>,
+        source: io::Error,
+    },
+    ///A line is not valid UTF-8
+    Encoding { file: PathBuf, line: usize },
+    ///The header row lacks a column every pin needs
+    MissingColumn {
+        file: PathBuf,
+        line: usize,
+        column: &'static str,
+    },
+    ///A table row splits into a different number of cells than the header
+    ColumnCount {
+        file: PathBuf,
+        line: usize,
+        found: usize,
+        expected: usize,
+    },
+    ///No pin rows were found before the end of the file
+    EmptyTable { file: PathBuf, line: usize },
+}
+
+impl PinoutError {
+    pub fn file(&self) -> &Path {
+        match self {
+            PinoutError::Io { file, .. }
+            | PinoutError::Encoding { file, .. }
+            | PinoutError::MissingColumn { file, .. }
+            | PinoutError::ColumnCount { file, .. }
+            | PinoutError::EmptyTable { file, .. } => file,
+        }
+    }
+
+    ///1-based line number the error refers to
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            PinoutError::Io { line, .. } => *line,
+            PinoutError::Encoding { line, .. }
+            | PinoutError::MissingColumn { line, .. }
+            | PinoutError::ColumnCount { line, .. }
+            | PinoutError::EmptyTable { line, .. } => Some(*line),
+        }
+    }
+}
+
+impl Display for PinoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // 与编译器相同的 file:line: message 格式
+        write!(f, "{}", self.file().display())?;
+        if let Some(line) = self.line() {
+            write!(f, ":{}", line)?;
+        }
+        match self {
+            PinoutError::Io { source, .. } => write!(f, ": {}", source),
+            PinoutError::Encoding { .. } => write!(f, ": line is not valid UTF-8"),
+            PinoutError::MissingColumn { column, .. } => {
+                write!(f, ": header has no {:?} column", column)
+            }
+            PinoutError::ColumnCount {
+                found, expected, ..
+            } => write!(f, ": {} columns, expected {}", found, expected),
+            PinoutError::EmptyTable { .. } => write!(f, ": no pin table found"),
+        }
+    }
+}
+
+impl Error for PinoutError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PinoutError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
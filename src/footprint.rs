@@ -0,0 +1,382 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, bail, Error};
+use regex::Regex;
+
+use crate::{pinout::Pin, sort::split_ball};
+
+///JEDEC BGA row letters, I, O, Q, S, X and Z are skipped
+const ROW_LETTERS: &str = "ABCDEFGHJKLMNPRTUVWY";
+
+///Zero based row index of a ball row, `A` is 0, `Y` is 19, `AA` is 20
+pub fn row_index(row: &str) -> Option<usize> {
+    let mut index = 0;
+    for (i, c) in row.chars().enumerate() {
+        let digit = ROW_LETTERS.find(c.to_ascii_uppercase())?;
+        // 多字母行号：A..Y 之后是 AA..AY, BA..
+        index = if i == 0 {
+            digit
+        } else {
+            (index + 1) * ROW_LETTERS.len() + digit
+        };
+    }
+    Some(index)
+}
+
+///Row letters for a zero based row index, inverse of [`row_index`]
+pub fn row_name(index: usize) -> String {
+    let letters: Vec<char> = ROW_LETTERS.chars().collect();
+    let n = letters.len();
+    if index < n {
+        letters[index].to_string()
+    } else {
+        format!("{}{}", row_name(index / n - 1), letters[index % n])
+    }
+}
+
+///Mechanical data of a BGA package (mm)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PackagePreset {
+    pub pitch: f64,
+    pub pad: f64,
+    ///Body edge length, packages are square
+    pub body: f64,
+}
+
+const fn bga(pitch: f64, pad: f64, body: f64) -> PackagePreset {
+    PackagePreset { pitch, pad, body }
+}
+
+///Known Xilinx packages, keyed by the package part of the device name
+const PRESETS: [(&str, PackagePreset); 16] = [
+    ("ffvb1156", bga(1.0, 0.5, 35.0)),
+    ("ffva1156", bga(1.0, 0.5, 35.0)),
+    ("ffvc1156", bga(1.0, 0.5, 35.0)),
+    ("ffg1156", bga(1.0, 0.5, 35.0)),
+    ("fbvb900", bga(1.0, 0.5, 31.0)),
+    ("ffvc900", bga(1.0, 0.5, 31.0)),
+    ("ffg900", bga(1.0, 0.5, 31.0)),
+    ("fbg676", bga(1.0, 0.5, 27.0)),
+    ("fgg676", bga(1.0, 0.5, 27.0)),
+    ("fgg484", bga(1.0, 0.5, 23.0)),
+    ("sfvc784", bga(0.8, 0.4, 23.0)),
+    ("sfva625", bga(0.8, 0.4, 21.0)),
+    ("clg484", bga(0.8, 0.4, 19.0)),
+    ("clg400", bga(0.8, 0.4, 17.0)),
+    ("csg324", bga(0.8, 0.4, 15.0)),
+    ("csg325", bga(0.8, 0.4, 15.0)),
+];
+
+///Package code at the end of a device name, `ffvb1156` for `xczu15egffvb1156`.
+///Known packages are matched first, others are guessed from the trailing
+///letters and digits.
+pub fn package_code(device: &str) -> Option<String> {
+    let device = device.to_ascii_lowercase();
+    if let Some((code, _)) = PRESETS.iter().find(|(code, _)| device.ends_with(code)) {
+        return Some(code.to_string());
+    }
+    let re = Regex::new(r"([a-z]{2,4}\d{3,4})$").unwrap();
+    re.captures(&device).map(|caps| caps[1].to_string())
+}
+
+///Preset for a package code or device name
+pub fn preset(name: &str) -> Option<PackagePreset> {
+    let name = name.to_ascii_lowercase();
+    PRESETS
+        .iter()
+        .find(|(code, _)| name.ends_with(code))
+        .map(|(_, preset)| *preset)
+}
+
+///Settings for [`write_kicad_mod`] (mm)
+#[derive(Clone, Debug)]
+pub struct FootprintOptions {
+    pub name: String,
+    pub description: String,
+    pub pitch: f64,
+    pub pad: f64,
+    ///Body size, derived from the ball grid when missing
+    pub body: Option<f64>,
+    ///Courtyard clearance around the body
+    pub courtyard: f64,
+    ///Silkscreen outline clearance around the body
+    pub silk_margin: f64,
+}
+
+///Ball positions of a package
+#[derive(Debug)]
+pub struct BallGrid {
+    pub rows: usize,
+    pub columns: usize,
+    ///Ball name, zero based row and column
+    pub balls: Vec<(String, usize, usize)>,
+}
+
+impl BallGrid {
+    ///Collect the balls from the `Pin` column
+    pub fn from_pins(pins: &[Pin]) -> Result<Self, Error> {
+        let mut balls = Vec::with_capacity(pins.len());
+        let mut seen = HashSet::new();
+        for pin in pins {
+            let (row, column) = split_ball(&pin.number)
+                .and_then(|(row, column)| Some((row_index(row)?, column.checked_sub(1)?)))
+                .ok_or_else(|| anyhow!("{:?} is not a BGA ball number", pin.number))?;
+            // 同一焊球只生成一个焊盘
+            if seen.insert(pin.number.clone()) {
+                balls.push((pin.number.clone(), row, column as usize));
+            }
+        }
+        if balls.is_empty() {
+            bail!("no balls found");
+        }
+
+        let rows = balls.iter().map(|b| b.1).max().unwrap() + 1;
+        let columns = balls.iter().map(|b| b.2).max().unwrap() + 1;
+        Ok(BallGrid {
+            rows,
+            columns,
+            balls,
+        })
+    }
+
+    ///Grid positions without a ball
+    pub fn depopulated(&self) -> Vec<(usize, usize)> {
+        let used: HashSet<(usize, usize)> = self.balls.iter().map(|b| (b.1, b.2)).collect();
+        (0..self.rows)
+            .flat_map(|row| (0..self.columns).map(move |column| (row, column)))
+            .filter(|pos| !used.contains(pos))
+            .collect()
+    }
+}
+
+///Format a coordinate without float noise
+fn num(value: f64) -> String {
+    let s = format!("{:.4}", value);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+fn push_line(fp: &mut String, start: (f64, f64), end: (f64, f64), width: f64, layer: &str) {
+    fp.push_str(&format!(
+        "  (fp_line (start {} {}) (end {} {})\n    (stroke (width {}) (type solid))\n    (layer \"{}\")\n  )\n",
+        num(start.0),
+        num(start.1),
+        num(end.0),
+        num(end.1),
+        num(width),
+        layer
+    ));
+}
+
+///Closed polygon as individual lines
+fn push_outline(fp: &mut String, points: &[(f64, f64)], width: f64, layer: &str) {
+    for (i, &start) in points.iter().enumerate() {
+        let end = points[(i + 1) % points.len()];
+        push_line(fp, start, end, width, layer);
+    }
+}
+
+fn push_text(fp: &mut String, key: &str, value: &str, y: f64, layer: &str) {
+    fp.push_str(&format!(
+        "  (property \"{}\" \"{}\"\n    (at 0 {} 0)\n    (layer \"{}\")\n    (effects (font (size 1 1) (thickness 0.15)))\n  )\n",
+        key,
+        value,
+        num(y),
+        layer
+    ));
+}
+
+///Render a KiCad 8 `.kicad_mod` footprint, pad numbers are the ball names
+pub fn write_kicad_mod(grid: &BallGrid, options: &FootprintOptions) -> String {
+    let pitch = options.pitch;
+    // A1 在左上角，网格中心为原点
+    let x0 = -(grid.columns as f64 - 1.0) * pitch / 2.0;
+    let y0 = -(grid.rows as f64 - 1.0) * pitch / 2.0;
+    let position =
+        |row: usize, column: usize| (x0 + column as f64 * pitch, y0 + row as f64 * pitch);
+
+    let half_w = options
+        .body
+        .map_or(grid.columns as f64 * pitch / 2.0 + pitch / 2.0, |b| b / 2.0);
+    let half_h = options
+        .body
+        .map_or(grid.rows as f64 * pitch / 2.0 + pitch / 2.0, |b| b / 2.0);
+    let depopulated = grid.depopulated();
+
+    let mut description = options.description.clone();
+    if !depopulated.is_empty() {
+        let names: Vec<String> = depopulated
+            .iter()
+            .map(|&(row, column)| format!("{}{}", row_name(row), column + 1))
+            .collect();
+        description.push_str(&format!(", depopulated: {}", names.join(" ")));
+    }
+
+    let mut fp = String::new();
+    fp.push_str(&format!("(footprint \"{}\"\n", options.name));
+    fp.push_str("  (version 20240108)\n");
+    fp.push_str("  (generator \"kicad-xilinx-symgen\")\n");
+    fp.push_str("  (layer \"F.Cu\")\n");
+    fp.push_str(&format!("  (descr \"{}\")\n", description));
+    fp.push_str("  (tags \"BGA Xilinx\")\n");
+    push_text(&mut fp, "Reference", "REF**", -half_h - 1.5, "F.SilkS");
+    push_text(&mut fp, "Value", &options.name, half_h + 1.5, "F.Fab");
+    fp.push_str("  (attr smd)\n");
+
+    // 器件外形，A1 角带倒角
+    let chamfer = 1.0_f64.min(half_w / 2.0);
+    push_outline(
+        &mut fp,
+        &[
+            (-half_w + chamfer, -half_h),
+            (half_w, -half_h),
+            (half_w, half_h),
+            (-half_w, half_h),
+            (-half_w, -half_h + chamfer),
+        ],
+        0.1,
+        "F.Fab",
+    );
+
+    let silk = (half_w + options.silk_margin, half_h + options.silk_margin);
+    push_outline(
+        &mut fp,
+        &[
+            (-silk.0, -silk.1),
+            (silk.0, -silk.1),
+            (silk.0, silk.1),
+            (-silk.0, silk.1),
+        ],
+        0.12,
+        "F.SilkS",
+    );
+    // A1 标记
+    push_line(
+        &mut fp,
+        (-silk.0 - 0.5, -silk.1),
+        (-silk.0 - 0.5, -silk.1 + 1.0),
+        0.12,
+        "F.SilkS",
+    );
+
+    let court = (half_w + options.courtyard, half_h + options.courtyard);
+    push_outline(
+        &mut fp,
+        &[
+            (-court.0, -court.1),
+            (court.0, -court.1),
+            (court.0, court.1),
+            (-court.0, court.1),
+        ],
+        0.05,
+        "F.CrtYd",
+    );
+
+    // 缺球位置在 Fab 层画叉
+    let mark = options.pad / 2.0;
+    for &(row, column) in &depopulated {
+        let (x, y) = position(row, column);
+        push_line(
+            &mut fp,
+            (x - mark, y - mark),
+            (x + mark, y + mark),
+            0.1,
+            "F.Fab",
+        );
+        push_line(
+            &mut fp,
+            (x - mark, y + mark),
+            (x + mark, y - mark),
+            0.1,
+            "F.Fab",
+        );
+    }
+
+    let mut balls: Vec<&(String, usize, usize)> = grid.balls.iter().collect();
+    balls.sort_by_key(|b| (b.1, b.2));
+    for (name, row, column) in balls {
+        let (x, y) = position(*row, *column);
+        fp.push_str(&format!(
+            "  (pad \"{}\" smd circle\n    (at {} {})\n    (size {} {})\n    (layers \"F.Cu\" \"F.Paste\" \"F.Mask\")\n  )\n",
+            name,
+            num(x),
+            num(y),
+            num(options.pad),
+            num(options.pad)
+        ));
+    }
+
+    fp.push_str(")\n");
+    fp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jedec_rows_skip_ambiguous_letters() {
+        assert_eq!(row_index("A"), Some(0));
+        assert_eq!(row_index("H"), Some(7));
+        assert_eq!(row_index("J"), Some(8));
+        assert_eq!(row_index("Y"), Some(19));
+        assert_eq!(row_index("AA"), Some(20));
+        assert_eq!(row_index("AP"), Some(33));
+        assert_eq!(row_index("I"), None);
+        for i in 0..60 {
+            assert_eq!(row_index(&row_name(i)), Some(i));
+        }
+    }
+
+    #[test]
+    fn pads_and_depopulated_marks() {
+        use crate::pinout::IoType;
+
+        // 3x3 grid without B2 and C1
+        let pins: Vec<Pin> = ["A1", "A2", "A3", "B1", "B3", "C2", "C3"]
+            .iter()
+            .map(|ball| Pin::test(ball, "GND", None, IoType::Na))
+            .collect();
+        let grid = BallGrid::from_pins(&pins).unwrap();
+        assert_eq!((grid.rows, grid.columns), (3, 3));
+        assert_eq!(grid.depopulated(), vec![(1, 1), (2, 0)]);
+
+        let options = FootprintOptions {
+            name: "TEST".to_string(),
+            description: "Test BGA".to_string(),
+            pitch: 0.8,
+            pad: 0.4,
+            body: None,
+            courtyard: 1.0,
+            silk_margin: 0.1,
+        };
+        let fp = write_kicad_mod(&grid, &options);
+
+        assert!(fp.contains("  (descr \"Test BGA, depopulated: B2 C1\")\n"));
+        assert!(fp.contains("  (pad \"A1\" smd circle\n    (at -0.8 -0.8)\n    (size 0.4 0.4)\n"));
+        assert!(fp.contains("  (pad \"C3\" smd circle\n    (at 0.8 0.8)\n"));
+        assert!(!fp.contains("(pad \"B2\""));
+        assert_eq!(fp.matches("(pad ").count(), 7);
+        // B2 在原点，画两条对角线
+        assert!(fp.contains("(start -0.2 -0.2) (end 0.2 0.2)"));
+        assert!(fp.contains("(start -0.2 0.2) (end 0.2 -0.2)"));
+        assert!(fp.contains("(start -1 0.6) (end -0.6 1)"));
+        assert!(fp.ends_with(")\n)\n"));
+    }
+
+    #[test]
+    fn presets_from_device_name() {
+        assert_eq!(
+            package_code("xczu15egffvb1156").as_deref(),
+            Some("ffvb1156")
+        );
+        assert_eq!(preset("xczu15egffvb1156").unwrap().body, 35.0);
+        assert_eq!(package_code("xc7a100tcsg324").as_deref(), Some("csg324"));
+        assert_eq!(preset("xc7a100tcsg324").unwrap().pitch, 0.8);
+        assert!(preset("xc7z999zzz1").is_none());
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use crate::{layout, pinout::Pin, sort};
+
+///A named set of pins that becomes one symbol unit
+pub type Group = (String, Vec<Pin>);
+
+///Group pins by the key `key_of` returns, keys listed in `order` come first,
+///the rest follow in natural order
+pub fn group_pins<F>(pins: Vec<Pin>, key_of: F, order: &[String]) -> Vec<Group>
+where
+    F: Fn(&Pin) -> String,
+{
+    let mut grouped: HashMap<String, Vec<Pin>> = HashMap::new();
+    for pin in pins {
+        grouped.entry(key_of(&pin)).or_default().push(pin);
+    }
+
+    // 固定单元顺序，保证输出稳定
+    let mut keys: Vec<String> = grouped.keys().cloned().collect();
+    sort::order_keys(&mut keys, order);
+    keys.into_iter()
+        .map(|key| {
+            let group = grouped.remove(&key).unwrap();
+            (key, group)
+        })
+        .collect()
+}
+
+///Leading part of a pin name before its first digit, `IO_L` for `IO_L10N_44`
+fn name_prefix(name: &str) -> &str {
+    let end = name
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(name.len());
+    &name[..end]
+}
+
+///Split groups with more than `max_pins` rows into continuation units named
+///`<key> 1/N`, `<key> 2/N`, ... Pins for which `stacks` is true share a row
+///with the pins of the same name, as with `--stack-power`, and stay in the
+///same chunk. Chunks are balanced and prefer to break where the pin name
+///prefix changes, so `PS_DDR_DQ` and `PS_DDR_A` end up apart. Both legs of a
+///differential pair always stay in the same chunk.
+pub fn split_oversized<F>(groups: Vec<Group>, max_pins: usize, stacks: F) -> Vec<Group>
+where
+    F: Fn(&Pin) -> bool,
+{
+    let max_pins = max_pins.max(1);
+    let mut result = Vec::with_capacity(groups.len());
+
+    for (key, pins) in groups {
+        let rows = stack_rows(pins, &stacks);
+        if rows.len() <= max_pins {
+            result.push((key, rows.into_iter().flatten().collect()));
+            continue;
+        }
+
+        let chunks = split_rows(rows, max_pins);
+        let total = chunks.len();
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let pins = chunk.into_iter().flatten().collect();
+            result.push((format!("{} {}/{}", key, i + 1, total), pins));
+        }
+    }
+
+    result
+}
+
+///Pins that take one row of the unit, stacked pins join the row of the first
+///pin with their name
+fn stack_rows<F>(pins: Vec<Pin>, stacks: &F) -> Vec<Vec<Pin>>
+where
+    F: Fn(&Pin) -> bool,
+{
+    let mut rows: Vec<Vec<Pin>> = Vec::with_capacity(pins.len());
+    let mut stacked: HashMap<String, usize> = HashMap::new();
+    for pin in pins {
+        if stacks(&pin) {
+            if let Some(&row) = stacked.get(&pin.name) {
+                rows[row].push(pin);
+                continue;
+            }
+            stacked.insert(pin.name.clone(), rows.len());
+        }
+        rows.push(vec![pin]);
+    }
+    rows
+}
+
+///`splits[i]` is true when cutting before row `i` would separate the legs of
+///a differential pair
+fn pair_splits(rows: &[Vec<Pin>]) -> Vec<bool> {
+    let mut legs: HashMap<String, (usize, usize)> = HashMap::new();
+    for (i, row) in rows.iter().enumerate() {
+        if let Some((key, _)) = layout::diff_pair(&row[0].name) {
+            legs.entry(key)
+                .and_modify(|span| span.1 = i)
+                .or_insert((i, i));
+        }
+    }
+    let mut splits = vec![false; rows.len() + 1];
+    for (first, last) in legs.into_values() {
+        for split in &mut splits[first + 1..=last] {
+            *split = true;
+        }
+    }
+    splits
+}
+
+fn split_rows(mut rows: Vec<Vec<Pin>>, max_rows: usize) -> Vec<Vec<Vec<Pin>>> {
+    let mut chunks = Vec::with_capacity(rows.len().div_ceil(max_rows));
+    while rows.len() > max_rows {
+        // 每次按剩余行数重新计算目标大小，避免最后剩下一个很小的单元
+        let remaining = rows.len().div_ceil(max_rows);
+        let target = rows.len().div_ceil(remaining);
+        // 在目标大小附近寻找前缀变化的位置，最多回退四分之一，
+        // 且剩下的行仍能放进剩余的单元
+        let lowest = (target - target / 4).max(rows.len() - (remaining - 1) * max_rows);
+        let splits = pair_splits(&rows);
+        let prefix = |i: usize| name_prefix(&rows[i][0].name);
+        let candidates = (lowest..=target).rev().filter(|&i| !splits[i]);
+        let cut = candidates
+            .clone()
+            .find(|&i| prefix(i - 1) != prefix(i))
+            .or_else(|| candidates.clone().next())
+            // 差分对占满了整个范围时再往前退
+            .or_else(|| (1..lowest).rev().find(|&i| !splits[i]))
+            .unwrap_or(target);
+        let rest = rows.split_off(cut);
+        chunks.push(rows);
+        rows = rest;
+    }
+    chunks.push(rows);
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinout::IoType;
+
+    fn pins(names: &[&str]) -> Vec<Pin> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Pin::test(&format!("A{}", i + 1), name, Some(503), IoType::PsConfig))
+            .collect()
+    }
+
+    fn sizes(groups: &[Group]) -> Vec<(&str, usize)> {
+        groups
+            .iter()
+            .map(|(key, pins)| (key.as_str(), pins.len()))
+            .collect()
+    }
+
+    #[test]
+    fn small_groups_are_kept() {
+        let groups = split_oversized(
+            vec![("0".to_string(), pins(&["DONE_0", "INIT_B_0"]))],
+            2,
+            |_| false,
+        );
+        assert_eq!(sizes(&groups), vec![("0", 2)]);
+    }
+
+    #[test]
+    fn fills_every_unit_when_the_prefix_changes_early() {
+        // bank 503: 18 PS_* pins, then two VCCO_PSIO3_503
+        let mut names: Vec<String> = (0..18).map(|i| format!("PS_MODE{}_503", i)).collect();
+        names[8] = "PS_DONE_503".to_string();
+        names.extend(["VCCO_PSIO3_503".to_string(), "VCCO_PSIO3_503".to_string()]);
+        let names: Vec<&str> = names.iter().map(|s| s.as_str()).collect();
+
+        let groups = split_oversized(vec![("503".to_string(), pins(&names))], 10, |_| false);
+        assert_eq!(sizes(&groups), vec![("503 1/2", 10), ("503 2/2", 10)]);
+    }
+
+    #[test]
+    fn keeps_differential_pairs_together() {
+        let names: Vec<String> = (1..=10)
+            .flat_map(|i| {
+                [
+                    format!("IO_L{}N_T1U_N7_QBC_AD4N_64", i),
+                    format!("IO_L{}P_T1U_N6_QBC_AD4P_64", i),
+                ]
+            })
+            .collect();
+        let names: Vec<&str> = names.iter().map(|s| s.as_str()).collect();
+
+        for max_pins in [5, 7, 9, 11, 13] {
+            let groups =
+                split_oversized(vec![("64".to_string(), pins(&names))], max_pins, |_| false);
+            for (key, chunk) in &groups {
+                assert!(chunk.len() <= max_pins, "{} has {} pins", key, chunk.len());
+                let pairs: Vec<String> = chunk
+                    .iter()
+                    .map(|pin| layout::diff_pair(&pin.name).unwrap().0)
+                    .collect();
+                for pair in &pairs {
+                    let legs = pairs.iter().filter(|p| *p == pair).count();
+                    assert_eq!(legs, 2, "{} split at --max-pins {}", pair, max_pins);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn breaks_where_the_prefix_changes() {
+        let mut names = vec!["PS_DDR_A0"; 9];
+        names.extend(vec!["PS_DDR_DQ0"; 11]);
+        let groups = split_oversized(vec![("504".to_string(), pins(&names))], 12, |_| false);
+        assert_eq!(sizes(&groups), vec![("504 1/2", 9), ("504 2/2", 11)]);
+        assert!(groups[1].1.iter().all(|pin| pin.name == "PS_DDR_DQ0"));
+    }
+
+    #[test]
+    fn counts_stacked_rows() {
+        let mut names = vec!["GND"; 54];
+        names.extend(["VCCINT", "VCCINT", "VCCAUX"]);
+        let stacks = |pin: &Pin| pin.name.starts_with("GND") || pin.name.starts_with("VCC");
+
+        let groups = split_oversized(vec![("GND".to_string(), pins(&names))], 3, stacks);
+        assert_eq!(sizes(&groups), vec![("GND", 57)]);
+
+        // 没有堆叠时 57 个引脚要拆开，但同名的电源引脚总在同一个单元
+        let groups = split_oversized(vec![("GND".to_string(), pins(&names))], 2, stacks);
+        assert_eq!(sizes(&groups), vec![("GND 1/2", 56), ("GND 2/2", 1)]);
+        let groups = split_oversized(vec![("GND".to_string(), pins(&names))], 3, |_| false);
+        assert_eq!(groups.len(), 19);
+    }
+}
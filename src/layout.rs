@@ -0,0 +1,136 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+///Polarity of one leg of a differential pair
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Polarity {
+    P,
+    N,
+}
+
+///Xilinx differential pair naming patterns. `key` identifies the pair, `pol`
+///the leg, the remaining groups are copied into the key.
+fn pair_patterns() -> &'static [Regex] {
+    static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        [
+            // IO_L10P_AD2P_44 / IO_L10N_AD2N_44, the middle part may differ
+            r"^(?P<key>IO_L\d+)(?P<pol>[PN])_(?:.*_)?(?P<bank>\d+)$",
+            // MGTHRXP0_128, PS_MGTRTXN1_505
+            r"^(?P<key>(?:PS_)?MGT\w*?[RT]X)(?P<pol>[PN])(?P<lane>\d+_\d+)$",
+            // MGTREFCLK0P_128, PS_MGTREFCLK2N_505
+            r"^(?P<key>(?:PS_)?MGT\w*?REFCLK\d+)(?P<pol>[PN])(?P<bank>_\d+)$",
+            // PS_DDR_DQS_P3 / PS_DDR_DQS_N3, PS_DDR_CK_N0
+            r"^(?P<key>PS_DDR_(?:DQS|CK))_(?P<pol>[PN])(?P<index>\d+)$",
+            // PS_DDR_CK0 is the positive leg of PS_DDR_CK_N0
+            r"^(?P<key>PS_DDR_CK)(?P<index>\d+)$",
+            // DXP/DXN, VP/VN, VREFP/VREFN, 7-series adds the bank: DXP_0
+            r"^(?P<key>DX|V|VREF)(?P<pol>[PN])(?P<bank>_\d+)?$",
+        ]
+        .into_iter()
+        .map(|re| Regex::new(re).unwrap())
+        .collect()
+    })
+}
+
+///Pair key and polarity when `name` is one leg of a differential pair
+pub fn diff_pair(name: &str) -> Option<(String, Polarity)> {
+    pair_patterns().iter().find_map(|re| {
+        let caps = re.captures(name)?;
+        let polarity = match caps.name("pol").map(|m| m.as_str()) {
+            Some("N") => Polarity::N,
+            _ => Polarity::P,
+        };
+        let mut key = caps["key"].to_string();
+        for group in ["bank", "lane", "index"] {
+            if let Some(m) = caps.name(group) {
+                key.push('/');
+                key.push_str(m.as_str());
+            }
+        }
+        Some((key, polarity))
+    })
+}
+
+///Split a sequence of blocks into a left and a right column.
+///
+///Blocks are kept whole and in order, `heights` gives the rows each one needs.
+///Returns the index of the first block of the right column, chosen so the
+///taller column is as short as possible; on a tie the left column is taller.
+pub fn split_columns(heights: &[usize]) -> usize {
+    let total: usize = heights.iter().sum();
+    // (taller column, right column is taller, split)
+    let mut best = (usize::MAX, true, 0);
+    let mut left = 0;
+
+    for split in 0..=heights.len() {
+        let right = total - left;
+        // 高度相同时优先让左列更高，保持与单列时相同的阅读顺序
+        let candidate = (left.max(right), right > left, split);
+        if candidate < best {
+            best = candidate;
+        }
+        if split < heights.len() {
+            left += heights[split];
+        }
+    }
+
+    best.2
+}
+
+///Rows taken by the blocks before and after `split`
+pub fn column_heights(heights: &[usize], split: usize) -> (usize, usize) {
+    let (left, right) = heights.split_at(split);
+    (left.iter().sum(), right.iter().sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(a: &str, b: &str) {
+        let (key_a, pol_a) = diff_pair(a).unwrap();
+        let (key_b, pol_b) = diff_pair(b).unwrap();
+        assert_eq!(key_a, key_b, "{} / {}", a, b);
+        assert_eq!((pol_a, pol_b), (Polarity::P, Polarity::N), "{} / {}", a, b);
+    }
+
+    #[test]
+    fn detects_xilinx_pairs() {
+        pair("IO_L10P_AD2P_44", "IO_L10N_AD2N_44");
+        pair("IO_L1P_T0L_N0_DBC_AD7P_64", "IO_L1N_T0L_N1_DBC_AD7N_64");
+        pair("MGTHRXP0_128", "MGTHRXN0_128");
+        pair("PS_MGTRTXP3_505", "PS_MGTRTXN3_505");
+        pair("MGTREFCLK0P_128", "MGTREFCLK0N_128");
+        pair("PS_DDR_DQS_P3", "PS_DDR_DQS_N3");
+        pair("PS_DDR_CK0", "PS_DDR_CK_N0");
+        pair("DXP", "DXN");
+        pair("DXP_0", "DXN_0");
+        pair("IO_L1P_T0_AD4P_35", "IO_L1N_T0_AD4N_35");
+        pair("MGTPTXP0_216", "MGTPTXN0_216");
+    }
+
+    #[test]
+    fn keeps_lanes_and_banks_apart() {
+        assert_ne!(diff_pair("MGTHRXP0_128"), diff_pair("MGTHRXP1_128"));
+        assert_ne!(
+            diff_pair("IO_L10P_AD2P_44").unwrap().0,
+            diff_pair("IO_L10N_AD2N_45").unwrap().0
+        );
+        assert_eq!(diff_pair("IO_T0U_N12_VRP_64"), None);
+        assert_eq!(diff_pair("VCCINT"), None);
+    }
+
+    #[test]
+    fn split_keeps_blocks_whole() {
+        assert_eq!(split_columns(&[2, 2, 2]), 2);
+        assert_eq!(split_columns(&[1, 2, 2, 1]), 2);
+        assert_eq!(split_columns(&[]), 0);
+        // 12 pairs and a stacked VCCO: 12/13 beats 14/11
+        let bank = [2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1];
+        assert_eq!(split_columns(&bank), 6);
+        assert_eq!(column_heights(&bank, 6), (12, 13));
+        assert_eq!(split_columns(&[1, 1, 1]), 2);
+    }
+}
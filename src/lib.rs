@@ -0,0 +1,22 @@
+//! Parse Xilinx ASCII package pinout files and turn them into KiCad symbols.
+//!
+//! [`PinoutTable`] holds the typed pin list of one package, the [`symbol`]
+//! module lays pins out into units and [`writer`] renders the library file.
+
+pub mod config;
+pub mod diff;
+pub mod error;
+pub mod footprint;
+pub mod group;
+pub mod layout;
+pub mod netlist;
+pub mod pinout;
+pub mod pintype;
+pub mod sort;
+pub mod symbol;
+pub mod update;
+pub mod writer;
+pub mod xdc;
+
+pub use error::PinoutError;
+pub use pinout::{DroppedLine, IoType, PackageInfo, Pin, PinoutFormat, PinoutTable, Revision};
@@ -1,234 +1,777 @@
 use std::{
-    collections::HashMap,
-    fmt::Debug,
-    fs::File,
-    io::{self, BufRead, BufReader, Write},
-    path::PathBuf,
+    fs::{self, File},
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
 };
 
-use anyhow::Error;
-use clap::Parser;
-use regex::Regex;
-use term_size;
+use anyhow::{anyhow, bail, Context, Error};
+use clap::{Parser, Subcommand};
+
+use kicad_xilinx_symgen::{
+    config::{Config, GroupRules},
+    diff::PinoutDiff,
+    footprint::{self, BallGrid, FootprintOptions},
+    group,
+    netlist::Netlist,
+    pintype::PinTypeTable,
+    sort::{natural_cmp, SortOrder},
+    symbol::{LayoutOptions, Symbol, Unit},
+    update,
+    writer::Format,
+    xdc, PackageInfo, PinoutTable,
+};
+
+///How much is written to stderr, set once from --quiet/--verbose
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+static VERBOSITY: OnceLock<Verbosity> = OnceLock::new();
+
+fn verbosity() -> Verbosity {
+    VERBOSITY.get().copied().unwrap_or(Verbosity::Normal)
+}
+
+///Progress and summaries, hidden by --quiet
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if verbosity() >= Verbosity::Normal {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+///Warnings, hidden by --quiet
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        if verbosity() >= Verbosity::Normal {
+            eprintln!("warning: {}", format_args!($($arg)*));
+        }
+    };
+}
+
+///Details such as the grouped pin list, only with --verbose
+macro_rules! detail {
+    ($($arg:tt)*) => {
+        if verbosity() >= Verbosity::Verbose {
+            eprintln!($($arg)*);
+        }
+    };
+}
 
 #[derive(Parser)]
-#[command(version, about)]
+#[command(
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    ///Only print errors; the generated output is unaffected
+    quiet: bool,
+    #[arg(short, long, global = true)]
+    ///Also print the grouped pin list and other details
+    verbose: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    symbol: SymbolArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    ///Generate a BGA footprint (.kicad_mod) from the package ball list
+    Footprint(FootprintArgs),
+    ///Generate a commented Vivado XDC skeleton for every user I/O
+    Xdc(XdcArgs),
+    ///Write XDC pin assignments from the nets of a KiCad netlist
+    Annotate(AnnotateArgs),
+    ///Check the pin constraints of a Vivado XDC against the pinout
+    CheckXdc(CheckXdcArgs),
+    ///Compare the pinouts of two pin-compatible devices
+    Diff(DiffArgs),
+}
+
+// 默认命令：生成原理图符号
+#[derive(clap::Args)]
+struct SymbolArgs {
+    #[arg(required = true)]
+    ///Xilinx ASCII pinout files, or directories of `.txt` pinout files;
+    ///each device becomes one symbol of the library
+    files: Vec<PathBuf>,
+    #[command(flatten)]
+    options: SymbolOptions,
+}
+
+// 符号生成选项，diff 生成迁移符号时共用
+#[derive(clap::Args)]
+struct SymbolOptions {
+    #[arg(short, long)]
+    ///FPGA part name, defaults to the Device in the pinout header; only
+    ///with a single input file
+    name: Option<String>,
+    #[arg(short, long, value_enum)]
+    ///Output library format, defaults to the one matching the output
+    ///extension, or legacy
+    format: Option<Format>,
+    #[arg(short, long, value_name = "PATH")]
+    ///Output library file, `-` for stdout [default: output.lib]
+    output: Option<PathBuf>,
+    #[arg(long, value_name = "LIB", conflicts_with = "output")]
+    ///Replace or add only these devices' symbols in an existing library,
+    ///all other symbols are kept as they are
+    update: Option<PathBuf>,
+    #[arg(short, long, value_name = "FIELD", conflicts_with = "config")]
+    ///Column to group units by, as a header name (e.g. "Bank") or index
+    group_by: Option<String>,
+    #[arg(short, long, value_name = "FILE")]
+    ///TOML file with ordered grouping rules, replaces --group-by
+    config: Option<PathBuf>,
+    #[arg(short, long, value_name = "FIELD")]
+    ///Column to sort pins by within a unit, as a header name or index
+    sort_by: Option<String>,
+    #[arg(long, value_enum, default_value_t = SortOrder::Natural)]
+    ///How pins are compared when sorting within a unit
+    sort_order: SortOrder,
+    #[arg(long, value_name = "FILE")]
+    ///Extra pin type rules, `<type> <name regex> [I/O Type]` per line,
+    ///checked before the built-in ones
+    pin_types: Option<PathBuf>,
+    #[arg(long, value_name = "KEY,...", value_delimiter = ',')]
+    ///Units to place first, in this order; the rest follow in natural order
+    unit_order: Vec<String>,
+    #[command(flatten)]
+    footer: FooterCheck,
+    #[arg(long, value_name = "N")]
+    ///Split units with more pins than this into continuation units
+    max_pins: Option<usize>,
+    #[arg(long)]
+    ///Stack identically named power pins on one location, extra ones hidden
+    stack_power: bool,
+    #[arg(long, value_name = "MILS", default_value_t = 100)]
+    ///Grid the symbol body width is snapped to
+    grid: i32,
+}
+
+// 读取引脚文件的命令共用的参数
+#[derive(clap::Args)]
+struct FooterCheck {
+    #[arg(long)]
+    ///Keep going when the parsed pin count differs from the file footer, or
+    ///the footer is missing
+    allow_mismatch: bool,
+}
+
+#[derive(clap::Args)]
+struct PinoutFile {
+    ///Xilinx ASCII pinout file
     file: PathBuf,
+    #[command(flatten)]
+    footer: FooterCheck,
+}
+
+impl PinoutFile {
+    fn load(&self) -> Result<PinoutTable, Error> {
+        load_table(&self.file, &self.footer)
+    }
+}
+
+#[derive(clap::Args)]
+struct FootprintArgs {
+    #[command(flatten)]
+    pinout: PinoutFile,
+    #[arg(short, long, value_name = "PATH")]
+    ///Output footprint file, `-` for stdout [default: <package>.kicad_mod]
+    output: Option<PathBuf>,
     #[arg(short, long)]
-    ///FPGA part name
+    ///Footprint name, defaults to the package code, e.g. ffvb1156
     name: Option<String>,
+    #[arg(short, long)]
+    ///Package preset, defaults to the one matching the Device header
+    package: Option<String>,
+    #[arg(long, value_name = "MM")]
+    ///Ball pitch
+    pitch: Option<f64>,
+    #[arg(long, value_name = "MM")]
+    ///Pad diameter
+    pad: Option<f64>,
+    #[arg(long, value_name = "MM")]
+    ///Package body size, derived from the ball grid when unknown
+    body: Option<f64>,
+    #[arg(long, value_name = "MM", default_value_t = 1.0)]
+    ///Courtyard clearance around the body
+    courtyard: f64,
+    #[arg(long, value_name = "MM", default_value_t = 0.1)]
+    ///Silkscreen outline clearance around the body
+    silk_margin: f64,
 }
 
-///FSM States
-enum States {
-    SeekTable,
-    ReadHeader,
-    ReadTable,
-    END,
+#[derive(clap::Args)]
+struct XdcArgs {
+    #[command(flatten)]
+    pinout: PinoutFile,
+    #[arg(short, long, value_name = "PATH")]
+    ///Output XDC file, `-` for stdout [default: output.xdc]
+    output: Option<PathBuf>,
+    #[arg(long, value_name = "TYPE=STD", value_parser = parse_key_value)]
+    ///IOSTANDARD for a bank type instead of the default, e.g. HP=LVDS
+    iostandard: Vec<(String, String)>,
 }
 
-///Key-value pairs, each record indicates a pin and its attribute.
-///Each record is unique because of the Pin Name.
-struct Record {
-    ///Table headers and corresponding values
-    fields: HashMap<String, String>,
+///Parse a `KEY=VALUE` argument
+fn parse_key_value(s: &str) -> Result<(String, String), Error> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected KEY=VALUE, got {:?}", s))?;
+    Ok((key.trim().to_ascii_uppercase(), value.trim().to_string()))
 }
 
-impl Record {
-    ///Create a record from a parsed line
-    fn new(headers: &[String], values: &[&str]) -> Self {
-        let mut fields = HashMap::new();
-        for (header, value) in headers.iter().zip(values.iter()) {
-            fields.insert(header.clone(), value.to_string());
+#[derive(clap::Args)]
+struct AnnotateArgs {
+    #[command(flatten)]
+    pinout: PinoutFile,
+    ///KiCad netlist, S-expression (.net) or XML export
+    netlist: PathBuf,
+    #[arg(short, long)]
+    ///Symbol name to look for, defaults to the Device in the pinout header
+    name: Option<String>,
+    #[arg(short, long = "ref", value_name = "REF")]
+    ///Reference designator of the FPGA, e.g. U1, instead of matching the name
+    reference: Option<String>,
+    #[arg(short, long, value_name = "PATH")]
+    ///Output XDC file, `-` for stdout [default: output.xdc]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct DiffArgs {
+    ///Pinout file of the current device
+    old: PathBuf,
+    ///Pinout file of the device to migrate to
+    new: PathBuf,
+    #[arg(long)]
+    ///Also write a migration-safe symbol holding only the balls with the
+    ///same name and I/O type on both devices
+    symbol: bool,
+    #[command(flatten)]
+    options: SymbolOptions,
+}
+
+#[derive(clap::Args)]
+struct CheckXdcArgs {
+    #[command(flatten)]
+    pinout: PinoutFile,
+    ///Vivado constraint file
+    xdc: PathBuf,
+}
+
+///Resolve a field given by header name (case insensitive) or by index
+fn resolve_field(headers: &[String], spec: &str) -> Result<String, Error> {
+    let spec = spec.trim();
+    if let Some(header) = headers.iter().find(|h| h.eq_ignore_ascii_case(spec)) {
+        return Ok(header.clone());
+    }
+    match spec.parse::<usize>() {
+        Ok(index) if index < headers.len() => Ok(headers[index].clone()),
+        _ => Err(anyhow!(
+            "unknown field {:?}, available fields: {}",
+            spec,
+            headers.join(", ")
+        )),
+    }
+}
+
+///Ask the user for a field on the terminal, only when stdin is interactive
+fn prompt_field(headers: &[String], prompt: &str) -> Result<String, Error> {
+    if !io::stdin().is_terminal() {
+        bail!("stdin is not a terminal, pass --group-by and --sort-by");
+    }
+
+    eprintln!("\nAvailable fields:");
+    for (i, header) in headers.iter().enumerate() {
+        eprintln!("{}: {}", i, header);
+    }
+
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    resolve_field(headers, &input)
+}
+
+///Parse a pinout file, report dropped lines and check the pin count footer
+fn load_table(path: &Path, footer: &FooterCheck) -> Result<PinoutTable, Error> {
+    detail!("reading {}", path.display());
+    let table = PinoutTable::open(path)?;
+    let pins_count = table.pins.len();
+    detail!("{}: {} pinout", path.display(), table.format);
+
+    // 分隔线只在终端上显示
+    if let Some((width, _)) = term_size::dimensions_stderr() {
+        info!("{}", "-".repeat(width));
+    }
+    info!("total pins parsed: {}", pins_count);
+
+    for dropped in &table.dropped {
+        warning!("{}, line dropped: {}", dropped.error, dropped.text);
+    }
+    // 没有页脚时无法确认解析是否完整，同样视为不一致
+    let mismatch = match table.expected_pins {
+        Some(expected) if expected != pins_count => Some(format!(
+            "{} pins parsed but the footer says {}",
+            pins_count, expected
+        )),
+        Some(_) => None,
+        None => Some("no \"Total Number of Pins\" footer found".to_string()),
+    };
+    if let Some(message) = mismatch {
+        if !footer.allow_mismatch {
+            bail!("{}, pass --allow-mismatch to continue anyway", message);
         }
-        Record { fields }
+        warning!("{}", message);
     }
+
+    Ok(table)
 }
 
-impl Debug for Record {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        // 获取所有键并排序
-        let mut keys: Vec<&String> = self.fields.keys().collect();
-        keys.sort();
-        for key in keys {
-            let value = self.fields.get(key).unwrap();
-            write!(f, "{:?}: {:?} ", key, value)?;
+///Expand directories to the `.txt` files they contain, in name order
+fn input_files(inputs: &[PathBuf]) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if !input.is_dir() {
+            files.push(input.clone());
+            continue;
         }
-        Ok(())
+        let mut found: Vec<PathBuf> = fs::read_dir(input)
+            .with_context(|| format!("failed to read {}", input.display()))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<_, _>>()?;
+        found.retain(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("txt"))
+        });
+        if found.is_empty() {
+            bail!("no .txt pinout files in {}", input.display());
+        }
+        found.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+        files.extend(found);
+    }
+    Ok(files)
+}
+
+///Write generated text to a file, or to stdout for `-`
+fn write_output(path: &Path, text: &str) -> Result<(), Error> {
+    if path.as_os_str() == "-" {
+        io::stdout().write_all(text.as_bytes())?;
+    } else {
+        let mut file = File::create(path)?;
+        file.write_all(text.as_bytes())?;
     }
+    Ok(())
 }
 
 fn main() -> Result<(), Error> {
     let args = Args::parse();
-
-    let re_blank = Regex::new(r"^\s*$").unwrap();
-    let re_spilt_header = Regex::new(r"\s{2,}").unwrap();
-
-    let mut index = 0;
-    let mut pins_count: usize = 0;
-    let mut state = States::SeekTable;
-    let mut headers: Vec<String> = Vec::new();
-    let mut records: Vec<Record> = Vec::new();
-
-    let file = File::open(args.file)?;
-    let mut buf_reader = BufReader::new(file);
-
-    let mut lines_iter = buf_reader.lines().map(|l| l.unwrap()).enumerate();
-
-    while let Some((line_num, line)) = lines_iter.next() {
-        match state {
-            //Seek a blank line 
-            States::SeekTable => {
-                if re_blank.is_match(&line) {
-                    println!("{}", "-".repeat(term_size::dimensions().unwrap().0));
-                    index = line_num;
-                    state = States::ReadHeader;
-                }
-            }
-            States::ReadHeader => {
-                // 解析表头
-                headers = re_spilt_header
-                    .split(&line.trim())
-                    .map(|s| s.to_string())
-                    .collect();
-                state = States::ReadTable
-            }
-            States::ReadTable => {
-                if re_blank.is_match(&line) {
-                    println!("{}", "-".repeat(term_size::dimensions().unwrap().0));
-                    state = States::END;
-                    continue;
-                }
-                // 逐行解析数据
-                let values: Vec<&str> = re_spilt_header.split(line.trim()).collect();
-                if values.len() == headers.len() {
-                    let record = Record::new(&headers, &values);
-                    records.push(record);
-                }
-            }
-
-            States::END => {
-                pins_count = records.len();
-
-                println!("total pins parsed: {}", pins_count);
+    let verbosity = match (args.quiet, args.verbose) {
+        (true, _) => Verbosity::Quiet,
+        (_, true) => Verbosity::Verbose,
+        _ => Verbosity::Normal,
+    };
+    VERBOSITY.set(verbosity).unwrap();
+    match args.command {
+        Some(Command::Footprint(footprint)) => generate_footprint(footprint),
+        Some(Command::Xdc(xdc)) => generate_xdc(xdc),
+        Some(Command::Annotate(annotate)) => annotate_xdc(annotate),
+        Some(Command::CheckXdc(check)) => check_xdc(check),
+        Some(Command::Diff(diff)) => diff_pinouts(diff),
+        None => {
+            let mut tables = Vec::new();
+            for file in input_files(&args.symbol.files)? {
+                let table = load_table(&file, &args.symbol.options.footer)?;
+                tables.push((file, table));
             }
+            generate_symbol(args.symbol.options, tables)
         }
     }
+}
 
-    println!("\nAvailable fields:");
-    for (i, header) in headers.iter().enumerate() {
-        println!("{}: {}", i, header);
+fn generate_footprint(args: FootprintArgs) -> Result<(), Error> {
+    let table = args.pinout.load()?;
+    let device = table.info.device.clone().unwrap_or_default();
+
+    let package = args.package.as_deref().unwrap_or(&device);
+    let preset = footprint::preset(package);
+    if preset.is_none() && (args.pitch.is_none() || args.pad.is_none()) {
+        bail!(
+            "no package preset for {:?}, pass --package or --pitch and --pad",
+            package
+        );
     }
+    let code = footprint::package_code(package).unwrap_or(device.clone());
+
+    let grid = BallGrid::from_pins(&table.pins)?;
+    let options = FootprintOptions {
+        name: args.name.unwrap_or(code.to_ascii_uppercase()),
+        description: format!("Xilinx {} BGA, {}x{} grid", device, grid.rows, grid.columns),
+        pitch: args.pitch.or(preset.map(|p| p.pitch)).unwrap(),
+        pad: args.pad.or(preset.map(|p| p.pad)).unwrap(),
+        body: args.body.or(preset.map(|p| p.body)),
+        courtyard: args.courtyard,
+        silk_margin: args.silk_margin,
+    };
+    let kicad_mod = footprint::write_kicad_mod(&grid, &options);
+
+    let output = args
+        .output
+        .unwrap_or_else(|| PathBuf::from(format!("{}.kicad_mod", options.name)));
+    write_output(&output, &kicad_mod)?;
+
+    info!("Finished Generation");
+    info!(
+        "{} pads, {} depopulated positions",
+        grid.balls.len(),
+        grid.depopulated().len()
+    );
+    Ok(())
+}
 
-    print!("Enter the number of the field to group by: ");
-    io::stdout().flush().unwrap();
+fn generate_xdc(args: XdcArgs) -> Result<(), Error> {
+    let table = args.pinout.load()?;
+    let overrides = args.iostandard.into_iter().collect();
+    let constraints = xdc::write_template(&table, &overrides);
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    let field_index: usize = input.trim().parse().unwrap();
+    let output = args.output.unwrap_or_else(|| PathBuf::from("output.xdc"));
+    write_output(&output, &constraints)?;
+
+    info!("Finished Generation");
+    info!(
+        "{} user I/O pins",
+        table.pins.iter().filter(|pin| xdc::is_user_io(pin)).count()
+    );
+    Ok(())
+}
 
-    if field_index >= headers.len() {
-        eprintln!("Invalid field index");
+fn annotate_xdc(args: AnnotateArgs) -> Result<(), Error> {
+    let table = args.pinout.load()?;
+    let name = args
+        .name
+        .or(table.info.device.clone())
+        .unwrap_or("XilinxFPGA".to_string());
+
+    let netlist = Netlist::open(&args.netlist)?;
+    let component = netlist.find_component(&name, args.reference.as_deref())?;
+    let nets = netlist.pin_nets(&component.reference);
+    let (constraints, assigned) = xdc::write_assignments(&table, &nets);
+
+    let output = args.output.unwrap_or_else(|| PathBuf::from("output.xdc"));
+    write_output(&output, &constraints)?;
+
+    info!("Finished Generation");
+    info!(
+        "{} user I/O pins of {} assigned from {} connected pins",
+        assigned,
+        component.reference,
+        nets.len()
+    );
+    Ok(())
+}
+
+fn check_xdc(args: CheckXdcArgs) -> Result<(), Error> {
+    let table = args.pinout.load()?;
+    let text = fs::read_to_string(&args.xdc)
+        .with_context(|| format!("failed to read {}", args.xdc.display()))?;
+    let constraints = xdc::parse_constraints(&text);
+    let issues = xdc::check(&table, &constraints);
+
+    for issue in &issues {
+        println!("{}:{}: {}", args.xdc.display(), issue.line, issue.message);
+    }
+    info!(
+        "{} constraints checked, {} problems found",
+        constraints.len(),
+        issues.len()
+    );
+    if !issues.is_empty() {
+        bail!("{} does not match the pinout", args.xdc.display());
+    }
+    Ok(())
+}
+
+fn diff_pinouts(args: DiffArgs) -> Result<(), Error> {
+    let old = load_table(&args.old, &args.options.footer)?;
+    let new = load_table(&args.new, &args.options.footer)?;
+    let name_of = |table: &PinoutTable, path: &Path| {
+        table
+            .info
+            .device
+            .clone()
+            .unwrap_or(path.display().to_string())
+    };
+    let (old_name, new_name) = (name_of(&old, &args.old), name_of(&new, &args.new));
+
+    let diff = PinoutDiff::new(&old, &new);
+    if diff.is_empty() {
+        info!("{} and {} have identical pinouts", old_name, new_name);
+    }
+    print!("{}", diff.report(&old_name, &new_name));
+    info!(
+        "{} balls changed, {} only in {}, {} only in {}, {} shared",
+        diff.changed.len(),
+        diff.only_old.len(),
+        old_name,
+        diff.only_new.len(),
+        new_name,
+        diff.shared.len()
+    );
+
+    if args.symbol {
+        let mut options = args.options;
+        options.name = options.name.or(Some(format!("{}_{}", old_name, new_name)));
+        // 迁移符号只保留两种器件共有的引脚
+        let table = PinoutTable {
+            info: PackageInfo {
+                device: Some(format!("{} / {}", old_name, new_name)),
+                ..Default::default()
+            },
+            pins: diff.shared,
+            ..old
+        };
+        generate_symbol(options, vec![(args.old, table)])?;
+    }
+    Ok(())
+}
+
+fn generate_symbol(args: SymbolOptions, tables: Vec<(PathBuf, PinoutTable)>) -> Result<(), Error> {
+    if args.name.is_some() && tables.len() > 1 {
+        bail!("--name only applies to a single input file");
+    }
+    let mut pin_types = PinTypeTable::builtin();
+    if let Some(path) = &args.pin_types {
+        pin_types = pin_types.with_overrides(PinTypeTable::load(path)?);
     }
 
-    let group_field = &headers[field_index];
+    // 分组规则只加载一次，所有器件共用
+    let rules = match &args.config {
+        Some(path) => Some(GroupRules::new(&Config::load(path)?)?),
+        None => None,
+    };
+
+    // 分组和排序字段只询问一次，按第一个文件的表头解析，所有器件共用
+    let headers = tables
+        .first()
+        .map(|(_, table)| table.headers.clone())
+        .unwrap_or_default();
+    let group_by = match (&args.config, &args.group_by) {
+        (Some(_), _) => None,
+        (None, Some(spec)) => Some(resolve_field(&headers, spec)?),
+        (None, None) => Some(prompt_field(
+            &headers,
+            "Enter the number of the field to group by: ",
+        )?),
+    };
+    let sort_by = match &args.sort_by {
+        Some(spec) => resolve_field(&headers, spec)?,
+        None => prompt_field(
+            &headers,
+            "Enter the number of the field to sort by within groups: ",
+        )?,
+    };
+
+    // 每个器件一个符号，重复的器件只保留第一个
+    let mut symbols: Vec<Symbol> = Vec::new();
+    let mut sources: Vec<PathBuf> = Vec::new();
+    let mut pins_count = 0;
+    let mut units_count = 0;
+    for (path, table) in tables {
+        let name = args
+            .name
+            .clone()
+            .or(table.info.device.clone())
+            .or(path.file_stem().map(|s| s.to_string_lossy().into_owned()))
+            .unwrap_or("XilinxFPGA".to_string());
+        if let Some(i) = symbols.iter().position(|s| s.name == name) {
+            warning!(
+                "skipping {}, {} was already generated from {}",
+                path.display(),
+                name,
+                sources[i].display()
+            );
+            continue;
+        }
 
-    // 根据用户选择的字段进行分组
-    let mut groups: HashMap<String, Vec<Record>> = HashMap::new();
+        for field in group_by.iter().chain([&sort_by]) {
+            if !table.headers.contains(field) {
+                bail!("{} has no {:?} column", path.display(), field);
+            }
+        }
 
-    for record in records {
-        let key = record.fields.get(group_field).unwrap().clone();
-        groups.entry(key).or_insert_with(Vec::new).push(record);
+        pins_count += table.pins.len();
+        let fields = (group_by.as_deref(), sort_by.as_str());
+        let symbol = build_symbol(&args, table, name, fields, rules.as_ref(), &pin_types)?;
+        units_count += symbol.units.len();
+        symbols.push(symbol);
+        sources.push(path);
     }
 
-    // 让用户选择排序字段
-    print!("Enter the number of the field to sort by within groups: ");
-    io::stdout().flush().unwrap();
+    let format = args
+        .format
+        .or_else(|| {
+            args.update
+                .as_deref()
+                .or(args.output.as_deref())
+                .and_then(Format::from_path)
+        })
+        .unwrap_or(Format::Legacy);
+
+    if let Some(path) = &args.update {
+        update_library(path, format, &symbols)?;
+    } else {
+        // 将字符串写入库文件
+        let kicad_lib = format.render(&symbols);
+        let output = args
+            .output
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(format!("output.{}", format.extension())));
+        write_output(&output, &kicad_lib)?;
+        if output.as_os_str() != "-" {
+            if let Some(doc) = format.render_doc(&symbols) {
+                write_output(&output.with_extension("dcm"), &doc)?;
+            }
+        }
+    }
 
-    input.clear();
-    io::stdin().read_line(&mut input).unwrap();
-    let sort_field_index: usize = input.trim().parse().unwrap();
+    info!("Finished Generation");
+    info!(
+        "{} pins parsed {} units generated in {} symbols",
+        pins_count,
+        units_count,
+        symbols.len()
+    );
 
-    if sort_field_index >= headers.len() {
-        eprintln!("Invalid field index");
+    Ok(())
+}
+
+///Splice the symbols into an existing library and its `.dcm`, files that do
+///not exist yet are created
+fn update_library(path: &Path, format: Format, symbols: &[Symbol]) -> Result<(), Error> {
+    let read = |path: &Path| -> Result<Option<String>, Error> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Ok(Some(text))
+    };
+
+    match read(path)? {
+        Some(existing) => {
+            let result = update::update_library(format, &existing, symbols)
+                .with_context(|| format!("failed to update {}", path.display()))?;
+            for name in &result.replaced {
+                info!("{}: replaced {}", path.display(), name);
+            }
+            for name in &result.inserted {
+                info!("{}: added {}", path.display(), name);
+            }
+            write_output(path, &result.text)?;
+        }
+        None => {
+            info!("{} does not exist, creating it", path.display());
+            write_output(path, &format.render(symbols))?;
+        }
     }
 
-    let sort_field = &headers[sort_field_index];
+    if format == Format::Legacy {
+        let dcm = path.with_extension("dcm");
+        let doc = match read(&dcm)? {
+            Some(existing) => {
+                update::update_legacy_dcm(&existing, symbols)
+                    .with_context(|| format!("failed to update {}", dcm.display()))?
+                    .text
+            }
+            None => format.render_doc(symbols).unwrap_or_default(),
+        };
+        write_output(&dcm, &doc)?;
+    }
+    Ok(())
+}
+
+///Group, sort and lay out the pins of one device. `fields` holds the group
+///header, None with --config, and the sort header. `rules` are the compiled
+///--config rules.
+fn build_symbol(
+    args: &SymbolOptions,
+    table: PinoutTable,
+    name: String,
+    fields: (Option<&str>, &str),
+    rules: Option<&GroupRules>,
+    pin_types: &PinTypeTable,
+) -> Result<Symbol, Error> {
+    let (group_by, sort_field) = fields;
+
+    // 根据配置规则或用户选择的字段进行分组
+    let (mut groups, group_field) = match (rules, &args.config) {
+        (Some(rules), Some(path)) => {
+            let groups = group::group_pins(table.pins, |pin| rules.unit_for(pin), &args.unit_order);
+            (groups, format!("rules in {}", path.display()))
+        }
+        _ => {
+            let group_field = group_by.unwrap_or_default().to_string();
+            let groups = group::group_pins(
+                table.pins,
+                |pin| pin.field(&group_field).unwrap_or_default(),
+                &args.unit_order,
+            );
+            (groups, group_field)
+        }
+    };
+
+    for (_, group) in groups.iter_mut() {
+        group.sort_by(|a, b| {
+            let a = a.field(sort_field).unwrap_or_default();
+            let b = b.field(sort_field).unwrap_or_default();
+            args.sort_order.compare(sort_field, &a, &b)
+        });
+    }
+    let layout = LayoutOptions {
+        stack_power: args.stack_power,
+        grid: args.grid,
+        // 规则生成的单元名已经是完整名称
+        title_prefix: if args.config.is_some() { "" } else { "Group" },
+        ..Default::default()
+    };
+    if let Some(max_pins) = args.max_pins {
+        // 按堆叠后的行数拆分
+        groups = group::split_oversized(groups, max_pins, |pin| {
+            layout.stacks(pin_types.classify(&pin.name, pin.io_type.as_str()))
+        });
+    }
 
     // 打印分组并排序后的数据
-    println!(
+    detail!(
         "\nGrouped and sorted data by {} and {}:",
-        group_field, sort_field
+        group_field,
+        sort_field
     );
-    for (key, group) in &mut groups {
-        println!("Group {}: ", key);
-        group.sort_by(|a, b| {
-            a.fields
-                .get(sort_field)
-                .unwrap()
-                .cmp(b.fields.get(sort_field).unwrap())
-        });
-        for record in group {
-            println!("{:?}", record);
-        }
-    }
-
-    // 生成 KiCad 库文件
-    let mut kicad_lib = String::new();
-    kicad_lib.push_str("EESchema-LIBRARY Version 2.4\n#encoding utf-8\n");
-
-    let mut unit_number = 1;
-    kicad_lib.push_str(&format!(
-        "DEF {} U 0 40 Y Y {} L N\n",
-        args.name.unwrap_or("XilinxFPGA".to_string()),
-        groups.len()
-    ));
-    kicad_lib.push_str(&format!("F0 \"U\" 0 300 50 H V C CNN\n"));
-    kicad_lib.push_str(&format!("F1 \"FPGA\" 0 200 50 H V C CNN\n"));
-    kicad_lib.push_str(&format!("F2 \"\" 0 0 50 H I C CNN\n"));
-    kicad_lib.push_str(&format!("F3 \"\" 0 0 50 H I C CNN\n"));
-    kicad_lib.push_str("DRAW\n");
-
-    for (key, group) in groups.iter() {
-    
-        for (i, record) in group.iter().enumerate() {
-            let pin = record.fields.get("Pin").unwrap();
-            let pin_name = record.fields.get("Pin Name").unwrap();
-            let posx = if i < group.len() / 2 { 0 } else { 3000 };
-            let posy = if i < group.len() / 2 {
-                i * 100
-            } else {
-                (i - group.len() / 2) * 100
-            };
-            let orientation = if i < group.len() / 2 { "R" } else { "L" };
-            kicad_lib.push_str(&format!(
-                "X {} {} {} -{} 150 {} 50 50 {} 1 P\n",
-                pin_name, pin, posx, posy, orientation, unit_number
-            ));
-        }
-
-        kicad_lib.push_str(&format!(
-            "S 150 150 2850 -{} {} 1 0 f\n",
-            group.len() / 2 * 100 + 50,
-            unit_number
-        ));
-        kicad_lib.push_str(&format!("T 0 -320 50 100 0 {} 1 Group{}\n", unit_number, key));
-
-        unit_number += 1;
-    }
-
-    kicad_lib.push_str("ENDDRAW\n");
-    kicad_lib.push_str("ENDDEF\n");
-    kicad_lib.push_str("#\n#End Library\n");
-
-    // 将字符串写入 .lib 文件
-    let filename = "output.lib";
-    let mut file = File::create(filename)?;
-    file.write_all(kicad_lib.as_bytes())?;
-
-    println!("Finished Generation");
-    println!("{} pins parsed {} units generated", pins_count, groups.len());
+    for (key, group) in &groups {
+        detail!("Group {}: ", key);
+        for pin in group {
+            detail!("{:?}", pin);
+        }
+    }
 
-    Ok(())
+    // 生成 KiCad 符号
+    let units = groups
+        .iter()
+        .map(|(key, group)| Unit::from_group(key, group, pin_types, &layout))
+        .collect();
+    Ok(Symbol::new(name, units).with_package_info(&table.info))
 }
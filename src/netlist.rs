@@ -0,0 +1,344 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{anyhow, bail, Context, Error};
+use regex::Regex;
+
+///A component of the netlist
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Component {
+    ///Reference designator, e.g. `U1`
+    pub reference: String,
+    pub value: String,
+    ///Symbol name from `libsource`
+    pub part: String,
+}
+
+///A net and the component pins on it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Net {
+    pub name: String,
+    ///Reference designator and pin number of every node
+    pub nodes: Vec<(String, String)>,
+}
+
+///KiCad netlist, read from the S-expression (`.net`) or the XML export
+#[derive(Debug, Default)]
+pub struct Netlist {
+    pub components: Vec<Component>,
+    pub nets: Vec<Net>,
+}
+
+impl Netlist {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    ///Parse either netlist format, detected from the first character
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        match text.trim_start().chars().next() {
+            Some('(') => Self::parse_sexpr(text),
+            Some('<') => Self::parse_xml(text),
+            _ => bail!("not a KiCad netlist"),
+        }
+    }
+
+    fn parse_sexpr(text: &str) -> Result<Self, Error> {
+        let root = Sexpr::parse(text)?;
+        if root.head() != Some("export") {
+            bail!("expected (export ...), found {:?}", root.head());
+        }
+
+        let mut netlist = Netlist::default();
+        for comp in root.find("components").iter().flat_map(|c| c.all("comp")) {
+            netlist.components.push(Component {
+                reference: comp.value("ref").unwrap_or_default().to_string(),
+                value: comp.value("value").unwrap_or_default().to_string(),
+                part: comp
+                    .find("libsource")
+                    .and_then(|lib| lib.value("part"))
+                    .unwrap_or_default()
+                    .to_string(),
+            });
+        }
+        for net in root.find("nets").iter().flat_map(|n| n.all("net")) {
+            netlist.nets.push(Net {
+                name: net.value("name").unwrap_or_default().to_string(),
+                nodes: net
+                    .all("node")
+                    .map(|node| {
+                        (
+                            node.value("ref").unwrap_or_default().to_string(),
+                            node.value("pin").unwrap_or_default().to_string(),
+                        )
+                    })
+                    .collect(),
+            });
+        }
+        Ok(netlist)
+    }
+
+    fn parse_xml(text: &str) -> Result<Self, Error> {
+        // 只需要 comp/value/libsource/net/node 几种标签，不引入完整的 XML 解析器
+        let tag = Regex::new(r"<(/?)([\w:]+)((?:[^>/]|/[^>])*)(/?)>").unwrap();
+        let attr = Regex::new(r#"([\w:]+)\s*=\s*"([^"]*)""#).unwrap();
+        let attrs = |s: &str| -> BTreeMap<String, String> {
+            attr.captures_iter(s)
+                .map(|c| (c[1].to_string(), xml_unescape(&c[2])))
+                .collect()
+        };
+
+        let mut netlist = Netlist::default();
+        if !tag
+            .captures(text)
+            .is_some_and(|c| &c[2] == "export" || &c[2] == "xml")
+        {
+            bail!("expected an <export> document");
+        }
+
+        let mut in_value = false;
+        let mut last_end = 0;
+        for caps in tag.captures_iter(text) {
+            let whole = caps.get(0).unwrap();
+            if in_value {
+                if let Some(comp) = netlist.components.last_mut() {
+                    comp.value = xml_unescape(text[last_end..whole.start()].trim());
+                }
+                in_value = false;
+            }
+            last_end = whole.end();
+
+            let closing = !caps[1].is_empty();
+            if closing {
+                continue;
+            }
+            let a = attrs(&caps[3]);
+            let get = |key: &str| a.get(key).cloned().unwrap_or_default();
+            match &caps[2] {
+                "comp" => netlist.components.push(Component {
+                    reference: get("ref"),
+                    ..Default::default()
+                }),
+                "value" => in_value = caps[4].is_empty(),
+                "libsource" => {
+                    if let Some(comp) = netlist.components.last_mut() {
+                        comp.part = get("part");
+                    }
+                }
+                "net" => netlist.nets.push(Net {
+                    name: get("name"),
+                    nodes: Vec::new(),
+                }),
+                "node" => {
+                    let net = netlist
+                        .nets
+                        .last_mut()
+                        .ok_or_else(|| anyhow!("<node> outside of a <net>"))?;
+                    net.nodes.push((get("ref"), get("pin")));
+                }
+                _ => {}
+            }
+        }
+        Ok(netlist)
+    }
+
+    ///Component built from the symbol `name`, matched against the library
+    ///part and the value (case insensitive), or the one with `reference`
+    pub fn find_component(&self, name: &str, reference: Option<&str>) -> Result<&Component, Error> {
+        if let Some(reference) = reference {
+            return self
+                .components
+                .iter()
+                .find(|c| c.reference == reference)
+                .ok_or_else(|| anyhow!("no component {} in the netlist", reference));
+        }
+
+        let matches: Vec<&Component> = self
+            .components
+            .iter()
+            .filter(|c| c.part.eq_ignore_ascii_case(name) || c.value.eq_ignore_ascii_case(name))
+            .collect();
+        match matches.as_slice() {
+            [component] => Ok(component),
+            [] => bail!("no component uses the symbol {}, pass --ref", name),
+            _ => bail!(
+                "several components use the symbol {}: {}, pass --ref",
+                name,
+                matches
+                    .iter()
+                    .map(|c| c.reference.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    ///Net on each pin of the component `reference`, keyed by pin number
+    pub fn pin_nets(&self, reference: &str) -> BTreeMap<String, String> {
+        let mut nets = BTreeMap::new();
+        for net in &self.nets {
+            for (node_ref, pin) in &net.nodes {
+                if node_ref == reference {
+                    nets.insert(pin.clone(), net.name.clone());
+                }
+            }
+        }
+        nets
+    }
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+///S-expression node of a `.net` file
+#[derive(Debug, PartialEq)]
+enum Sexpr {
+    Atom(String),
+    List(Vec<Sexpr>),
+}
+
+impl Sexpr {
+    fn parse(text: &str) -> Result<Self, Error> {
+        let mut chars = text.chars().peekable();
+        let mut stack: Vec<Vec<Sexpr>> = Vec::new();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '(' => stack.push(Vec::new()),
+                ')' => {
+                    let list = Sexpr::List(stack.pop().ok_or_else(|| anyhow!("unbalanced ')'"))?);
+                    match stack.last_mut() {
+                        Some(parent) => parent.push(list),
+                        None => return Ok(list),
+                    }
+                }
+                '"' => {
+                    let mut atom = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => atom.extend(chars.next()),
+                            Some(c) => atom.push(c),
+                            None => bail!("unterminated string"),
+                        }
+                    }
+                    stack
+                        .last_mut()
+                        .ok_or_else(|| anyhow!("string outside of a list"))?
+                        .push(Sexpr::Atom(atom));
+                }
+                c if c.is_whitespace() => {}
+                c => {
+                    let mut atom = c.to_string();
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() || c == '(' || c == ')' {
+                            break;
+                        }
+                        atom.push(c);
+                        chars.next();
+                    }
+                    stack
+                        .last_mut()
+                        .ok_or_else(|| anyhow!("atom outside of a list"))?
+                        .push(Sexpr::Atom(atom));
+                }
+            }
+        }
+        bail!("unexpected end of file")
+    }
+
+    fn items(&self) -> &[Sexpr] {
+        match self {
+            Sexpr::List(items) => items,
+            Sexpr::Atom(_) => &[],
+        }
+    }
+
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Sexpr::Atom(s) => Some(s),
+            Sexpr::List(_) => None,
+        }
+    }
+
+    ///First atom of a list, `export` for `(export ...)`
+    fn head(&self) -> Option<&str> {
+        self.items().first()?.atom()
+    }
+
+    ///Child lists starting with `name`
+    fn all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Sexpr> {
+        self.items().iter().filter(move |s| s.head() == Some(name))
+    }
+
+    fn find(&self, name: &str) -> Option<&Sexpr> {
+        self.items().iter().find(|s| s.head() == Some(name))
+    }
+
+    ///Value of a `(name value)` child
+    fn value(&self, name: &str) -> Option<&str> {
+        self.find(name)?.items().get(1)?.atom()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEXPR: &str = r#"(export (version "E")
+  (components
+    (comp (ref "U1")
+      (value "xczu15egffvb1156")
+      (libsource (lib "fpga") (part "xczu15egffvb1156") (description "")))
+    (comp (ref "R1") (value "10k")))
+  (nets
+    (net (code "1") (name "/LED0") (class "Default")
+      (node (ref "U1") (pin "AE10") (pintype "bidirectional"))
+      (node (ref "R1") (pin "1")))
+    (net (code 2) (name GND)
+      (node (ref U1) (pin A1)))))"#;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<export version="E">
+  <components>
+    <comp ref="U1">
+      <value>xczu15egffvb1156</value>
+      <libsource lib="fpga" part="xczu15egffvb1156" description=""/>
+    </comp>
+  </components>
+  <nets>
+    <net code="1" name="/LED0" class="Default">
+      <node ref="U1" pin="AE10" pintype="bidirectional"/>
+    </net>
+    <net code="2" name="A&amp;B">
+      <node ref="U1" pin="AF10"/>
+    </net>
+  </nets>
+</export>"#;
+
+    #[test]
+    fn reads_sexpr_netlist() {
+        let netlist = Netlist::parse(SEXPR).unwrap();
+        let u1 = netlist.find_component("XCZU15EGFFVB1156", None).unwrap();
+        assert_eq!(u1.reference, "U1");
+        let nets = netlist.pin_nets("U1");
+        assert_eq!(nets["AE10"], "/LED0");
+        assert_eq!(nets["A1"], "GND");
+    }
+
+    #[test]
+    fn reads_xml_netlist() {
+        let netlist = Netlist::parse(XML).unwrap();
+        assert_eq!(netlist.components[0].value, "xczu15egffvb1156");
+        assert_eq!(netlist.components[0].part, "xczu15egffvb1156");
+        let nets = netlist.pin_nets("U1");
+        assert_eq!(nets["AE10"], "/LED0");
+        assert_eq!(nets["AF10"], "A&B");
+    }
+}
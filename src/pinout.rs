@@ -0,0 +1,615 @@
+use std::{
+    convert::Infallible,
+    fmt::{self, Display},
+    fs::File,
+    io::{BufRead, BufReader, ErrorKind},
+    path::Path,
+    str::FromStr,
+};
+
+use regex::Regex;
+
+use crate::error::PinoutError;
+
+///Placeholder Xilinx uses for empty cells
+const NA: &str = "NA";
+
+///Columns every pin table must have
+const REQUIRED_COLUMNS: [&str; 2] = ["Pin", "Pin Name"];
+
+///Columns `Pin::field` knows, in the order of the UltraScale files
+pub const FIELDS: [&str; 8] = [
+    "Pin",
+    "Pin Name",
+    "Memory Byte Group",
+    "Bank",
+    "I/O Type",
+    "Super Logic Region",
+    "VCCAUX Group",
+    "No-Connect",
+];
+
+///Value of the `I/O Type` column
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IoType {
+    ///High performance PL bank
+    Hp,
+    ///High density PL bank
+    Hd,
+    ///High range PL bank (7-series)
+    Hr,
+    Gth,
+    Gty,
+    Gtx,
+    Gtp,
+    PsMio,
+    PsDdr,
+    PsGtr,
+    PsConfig,
+    Config,
+    ///Power, ground and analog pins
+    Na,
+    ///Anything this version does not know about, kept verbatim
+    Other(String),
+}
+
+impl IoType {
+    ///Text as it appears in the pinout file
+    pub fn as_str(&self) -> &str {
+        match self {
+            IoType::Hp => "HP",
+            IoType::Hd => "HD",
+            IoType::Hr => "HR",
+            IoType::Gth => "GTH",
+            IoType::Gty => "GTY",
+            IoType::Gtx => "GTX",
+            IoType::Gtp => "GTP",
+            IoType::PsMio => "PSMIO",
+            IoType::PsDdr => "PSDDR",
+            IoType::PsGtr => "PSGTR",
+            IoType::PsConfig => "PSCONFIG",
+            IoType::Config => "CONFIG",
+            IoType::Na => NA,
+            IoType::Other(s) => s,
+        }
+    }
+}
+
+impl FromStr for IoType {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_uppercase().as_str() {
+            "HP" => IoType::Hp,
+            "HD" => IoType::Hd,
+            "HR" => IoType::Hr,
+            "GTH" => IoType::Gth,
+            "GTY" => IoType::Gty,
+            "GTX" => IoType::Gtx,
+            "GTP" => IoType::Gtp,
+            "PSMIO" => IoType::PsMio,
+            "PSDDR" => IoType::PsDdr,
+            "PSGTR" => IoType::PsGtr,
+            "PSCONFIG" => IoType::PsConfig,
+            "CONFIG" => IoType::Config,
+            "NA" | "" => IoType::Na,
+            _ => IoType::Other(s.to_string()),
+        })
+    }
+}
+
+impl Display for IoType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+///One package pin and its attributes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pin {
+    ///Package ball, e.g. `AG13`
+    pub number: String,
+    ///Xilinx pin name, e.g. `IO_L10N_AD2N_44`
+    pub name: String,
+    pub byte_group: Option<String>,
+    pub bank: Option<u32>,
+    pub io_type: IoType,
+    ///Super Logic Region
+    pub slr: Option<String>,
+    ///VCCAUX_IO group of HP banks (7-series)
+    pub vccaux_group: Option<String>,
+    ///No-Connect column (7-series)
+    pub no_connect: Option<String>,
+}
+
+impl Pin {
+    ///Create a pin from the cells of a parsed line
+    fn new(headers: &[String], values: &[&str]) -> Self {
+        let mut pin = Pin {
+            number: String::new(),
+            name: String::new(),
+            byte_group: None,
+            bank: None,
+            io_type: IoType::Na,
+            slr: None,
+            vccaux_group: None,
+            no_connect: None,
+        };
+        for (header, value) in headers.iter().zip(values.iter()) {
+            let value = value.trim();
+            let optional = (value != NA).then(|| value.to_string());
+            match header.as_str() {
+                "Pin" => pin.number = value.to_string(),
+                "Pin Name" => pin.name = value.to_string(),
+                "Memory Byte Group" => pin.byte_group = optional,
+                "Bank" => pin.bank = value.parse().ok(),
+                "I/O Type" => pin.io_type = value.parse().unwrap(),
+                "Super Logic Region" => pin.slr = optional,
+                "VCCAUX Group" => pin.vccaux_group = optional,
+                "No-Connect" => pin.no_connect = optional,
+                _ => {}
+            }
+        }
+        pin
+    }
+
+    ///Value of a column by its header name, as written in the pinout file
+    pub fn field(&self, header: &str) -> Option<String> {
+        let optional = |v: &Option<String>| v.clone().unwrap_or_else(|| NA.to_string());
+        Some(match header {
+            "Pin" => self.number.clone(),
+            "Pin Name" => self.name.clone(),
+            "Memory Byte Group" => optional(&self.byte_group),
+            "Bank" => self.bank.map_or_else(|| NA.to_string(), |b| b.to_string()),
+            "I/O Type" => self.io_type.to_string(),
+            "Super Logic Region" => optional(&self.slr),
+            "VCCAUX Group" => optional(&self.vccaux_group),
+            "No-Connect" => optional(&self.no_connect),
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+impl Pin {
+    ///Pin with only the columns most tests care about, the others NA
+    pub(crate) fn test(number: &str, name: &str, bank: Option<u32>, io_type: IoType) -> Self {
+        Pin {
+            number: number.to_string(),
+            name: name.to_string(),
+            byte_group: None,
+            bank,
+            io_type,
+            slr: None,
+            vccaux_group: None,
+            no_connect: None,
+        }
+    }
+}
+
+///One entry of the Modification History
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Revision {
+    pub date: String,
+    pub revision: String,
+    pub status: String,
+    pub details: String,
+}
+
+///Metadata from the `--` comment header of a pinout file
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PackageInfo {
+    ///Part name, e.g. `xczu15egffvb1156`
+    pub device: Option<String>,
+    ///Generation date of the file
+    pub date: Option<String>,
+    pub revision: Option<String>,
+    ///`Production`, `Engineering Sample`, ...
+    pub status: Option<String>,
+    ///Modification History, oldest first
+    pub history: Vec<Revision>,
+}
+
+impl PackageInfo {
+    ///Pick up a `Key : value` pair from a header comment line
+    fn parse_line(&mut self, re_entry: &Regex, line: &str) {
+        let Some(caps) = re_entry.captures(line) else {
+            return;
+        };
+        let value = caps[3].trim().to_string();
+        let key = caps.get(2).map(|k| k.as_str());
+
+        // 以 "|" 开头的是修改记录
+        if caps.get(1).is_some() {
+            match key {
+                Some("Date") => self.history.push(Revision {
+                    date: value,
+                    ..Default::default()
+                }),
+                Some(key) => {
+                    let Some(entry) = self.history.last_mut() else {
+                        return;
+                    };
+                    match key {
+                        "Revision" => entry.revision = value,
+                        "Status" => entry.status = value,
+                        "Details" => entry.details = value,
+                        _ => {}
+                    }
+                }
+                // 续行，追加到 Details
+                None => {
+                    if let Some(entry) = self.history.last_mut() {
+                        if !value.is_empty() {
+                            if !entry.details.is_empty() {
+                                entry.details.push(' ');
+                            }
+                            entry.details.push_str(&value);
+                        }
+                    }
+                }
+            }
+            return;
+        }
+
+        match key {
+            Some("Device") => self.device = Some(value),
+            Some("Date") => self.date = Some(value),
+            Some("Revision") => self.revision = Some(value),
+            Some("Status") => self.status = Some(value),
+            _ => {}
+        }
+    }
+}
+
+///A table line that could not be turned into a pin
+#[derive(Debug)]
+pub struct DroppedLine {
+    pub text: String,
+    ///Why the line was skipped, with its file and line number
+    pub error: PinoutError,
+}
+
+///Family layout of a pinout file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PinoutFormat {
+    ///UltraScale and UltraScale+: `--` comment header with `Device :` lines
+    #[default]
+    UltraScale,
+    ///7-series: a single `Device/Package` line and the extra `VCCAUX Group`
+    ///and `No-Connect` columns
+    Series7,
+}
+
+impl PinoutFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PinoutFormat::UltraScale => "UltraScale",
+            PinoutFormat::Series7 => "7-series",
+        }
+    }
+}
+
+impl Display for PinoutFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+///Columns only 7-series files have
+const SERIES7_COLUMNS: [&str; 2] = ["VCCAUX Group", "No-Connect"];
+
+///FSM States
+enum States {
+    SeekTable,
+    ReadHeader,
+    ReadTable,
+    End,
+}
+
+///The pin table of a Xilinx ASCII pinout file
+#[derive(Debug)]
+pub struct PinoutTable {
+    ///Detected file layout
+    pub format: PinoutFormat,
+    ///Comment header metadata
+    pub info: PackageInfo,
+    ///Column headers in file order
+    pub headers: Vec<String>,
+    pub pins: Vec<Pin>,
+    ///Pin count from the `Total Number of Pins` footer
+    pub expected_pins: Option<usize>,
+    ///Table lines skipped while parsing
+    pub dropped: Vec<DroppedLine>,
+}
+
+impl PinoutTable {
+    ///Parse a pinout file from disk
+    pub fn open(path: &Path) -> Result<Self, PinoutError> {
+        let file = File::open(path).map_err(|source| PinoutError::Io {
+            file: path.to_path_buf(),
+            line: None,
+            source,
+        })?;
+        Self::parse(BufReader::new(file), path)
+    }
+
+    ///Parse a pinout file, the table starts after the first blank line.
+    ///`file` is only used in error messages.
+    pub fn parse<R: BufRead>(reader: R, file: &Path) -> Result<Self, PinoutError> {
+        let re_blank = Regex::new(r"^\s*$").unwrap();
+        let re_spilt_header = Regex::new(r"\s{2,}").unwrap();
+        let re_entry =
+            Regex::new(r"^--\s*(\|)?\s*(?:(Device|Date|Revision|Status|Details)\s*:)?(.*)$")
+                .unwrap();
+        // 7-series 文件头只有一行 Device/Package
+        let re_package = Regex::new(r"^Device/Package\s+(\S+)\s*(.*)$").unwrap();
+        let re_total = Regex::new(r"^\s*Total Number of Pins\s*[:,]?\s*(\d+)").unwrap();
+
+        let mut state = States::SeekTable;
+        let mut format = PinoutFormat::UltraScale;
+        let mut headers: Vec<String> = Vec::new();
+        let mut pins: Vec<Pin> = Vec::new();
+        let mut info = PackageInfo::default();
+        let mut expected_pins = None;
+        let mut dropped = Vec::new();
+        let mut header_line = 0;
+        let mut line_count = 0;
+
+        for (line_num, line) in reader.lines().enumerate() {
+            line_count = line_num + 1;
+            let line = line.map_err(|source| {
+                // 非 UTF-8 内容由 lines() 以 InvalidData 报告
+                if source.kind() == ErrorKind::InvalidData {
+                    PinoutError::Encoding {
+                        file: file.to_path_buf(),
+                        line: line_num + 1,
+                    }
+                } else {
+                    PinoutError::Io {
+                        file: file.to_path_buf(),
+                        line: Some(line_num + 1),
+                        source,
+                    }
+                }
+            })?;
+            if let Some(caps) = re_total.captures(&line) {
+                expected_pins = caps[1].parse().ok();
+                state = States::End;
+                continue;
+            }
+            match state {
+                //Seek a blank line
+                States::SeekTable => {
+                    info.parse_line(&re_entry, &line);
+                    if let Some(caps) = re_package.captures(line.trim()) {
+                        format = PinoutFormat::Series7;
+                        info.device.get_or_insert(caps[1].to_string());
+                        if !caps[2].trim().is_empty() {
+                            info.date.get_or_insert(caps[2].trim().to_string());
+                        }
+                    }
+                    if re_blank.is_match(&line) {
+                        state = States::ReadHeader;
+                    }
+                }
+                States::ReadHeader => {
+                    // 解析表头
+                    headers = re_spilt_header
+                        .split(line.trim())
+                        .map(|s| s.to_string())
+                        .collect();
+                    header_line = line_num + 1;
+                    if SERIES7_COLUMNS
+                        .iter()
+                        .any(|c| headers.iter().any(|h| h == c))
+                    {
+                        format = PinoutFormat::Series7;
+                    }
+                    for column in REQUIRED_COLUMNS {
+                        if !headers.iter().any(|h| h == column) {
+                            return Err(PinoutError::MissingColumn {
+                                file: file.to_path_buf(),
+                                line: header_line,
+                                column,
+                            });
+                        }
+                    }
+                    state = States::ReadTable
+                }
+                States::ReadTable => {
+                    if re_blank.is_match(&line) {
+                        state = States::End;
+                        continue;
+                    }
+                    // 逐行解析数据
+                    let values: Vec<&str> = re_spilt_header.split(line.trim()).collect();
+                    if values.len() == headers.len() {
+                        pins.push(Pin::new(&headers, &values));
+                    } else {
+                        dropped.push(DroppedLine {
+                            text: line.trim_end().to_string(),
+                            error: PinoutError::ColumnCount {
+                                file: file.to_path_buf(),
+                                line: line_num + 1,
+                                found: values.len(),
+                                expected: headers.len(),
+                            },
+                        });
+                    }
+                }
+                States::End => {}
+            }
+        }
+
+        if pins.is_empty() {
+            return Err(PinoutError::EmptyTable {
+                file: file.to_path_buf(),
+                line: if header_line > 0 {
+                    header_line
+                } else {
+                    line_count
+                },
+            });
+        }
+
+        Ok(PinoutTable {
+            format,
+            info,
+            headers,
+            pins,
+            expected_pins,
+            dropped,
+        })
+    }
+}
+
+#[cfg(test)]
+impl PinoutTable {
+    ///Table holding `pins` and nothing else
+    pub(crate) fn test(pins: Vec<Pin>) -> Self {
+        PinoutTable {
+            format: PinoutFormat::default(),
+            info: PackageInfo::default(),
+            headers: Vec::new(),
+            pins,
+            expected_pins: None,
+            dropped: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PINOUT: &str = "\
+--  Device/Package xczu15egffvb1156 4/25/2017 18:03:34
+--  Device : xczu15egffvb1156
+
+Pin  Pin Name           Memory Byte Group  Bank  I/O Type  Super Logic Region
+A1   GND                NA                 NA    NA        NA
+AN14  IO_L1P_AD11P_44   NA                 44    HD        NA
+AP14  IO_L1N_AD11N_44
+Total Number of Pins 3
+";
+
+    fn parse(text: &[u8]) -> Result<PinoutTable, PinoutError> {
+        PinoutTable::parse(text, Path::new("test.txt"))
+    }
+
+    #[test]
+    fn parses_pins_and_drops_short_rows() {
+        let table = parse(PINOUT.as_bytes()).unwrap();
+        assert_eq!(table.format, PinoutFormat::UltraScale);
+        assert_eq!(table.info.device.as_deref(), Some("xczu15egffvb1156"));
+        assert_eq!(table.pins.len(), 2);
+        assert_eq!(table.pins[1].bank, Some(44));
+        assert_eq!(table.pins[1].io_type, IoType::Hd);
+        assert_eq!(table.expected_pins, Some(3));
+        assert!(matches!(
+            table.dropped[0].error,
+            PinoutError::ColumnCount {
+                line: 7,
+                found: 2,
+                expected: 6,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn reads_modification_history() {
+        let header = "\
+--  Device   : xczu15egffvb1156
+--  Date     : 4/25/2017 18:03:34
+--  Revision : 1.1
+--  Status   : Production
+------------------------------------------------------------------------
+--  Modification History
+--  | Date    : 10/6/2016
+--  | Revision: 1.0
+--  | Status  : Engineering Sample
+--  | Details : Initial creation.
+------------------------------------------------------------------------
+--  | Date    : 4/25/2017
+--  | Revision: 1.1
+--  | Status  : Production
+--  | Details : Updated XCZU15EG
+--  |           to Production
+--  |
+";
+        let pins = &PINOUT[PINOUT.find("\nPin ").unwrap()..];
+        let table = parse(format!("{}{}", header, pins).as_bytes()).unwrap();
+        let info = &table.info;
+        assert_eq!(info.date.as_deref(), Some("4/25/2017 18:03:34"));
+        assert_eq!(info.revision.as_deref(), Some("1.1"));
+        assert_eq!(info.status.as_deref(), Some("Production"));
+        assert_eq!(
+            info.history,
+            vec![
+                Revision {
+                    date: "10/6/2016".to_string(),
+                    revision: "1.0".to_string(),
+                    status: "Engineering Sample".to_string(),
+                    details: "Initial creation.".to_string(),
+                },
+                Revision {
+                    date: "4/25/2017".to_string(),
+                    revision: "1.1".to_string(),
+                    status: "Production".to_string(),
+                    details: "Updated XCZU15EG to Production".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_series7_files() {
+        let text = "\
+Device/Package xc7a100tcsg324 10/22/2013 15:43:47
+
+Pin   Pin Name                  Memory Byte Group  Bank  VCCAUX Group  Super Logic Region  I/O Type  No-Connect
+A1    IO_L1N_T0_AD4N_35         0                  35    NA            NA                  HR        NA
+J10   DXP_0                     NA                 0     NA            NA                  NA        NA
+
+Total Number of Pins, 2
+";
+        let table = parse(text.as_bytes()).unwrap();
+        assert_eq!(table.format, PinoutFormat::Series7);
+        assert_eq!(table.info.device.as_deref(), Some("xc7a100tcsg324"));
+        assert_eq!(table.info.date.as_deref(), Some("10/22/2013 15:43:47"));
+        assert_eq!(table.expected_pins, Some(2));
+        assert_eq!(table.pins[0].io_type, IoType::Hr);
+        assert_eq!(table.pins[0].field("VCCAUX Group").as_deref(), Some("NA"));
+        assert_eq!(table.pins[1].bank, Some(0));
+        assert!(table.dropped.is_empty());
+    }
+
+    #[test]
+    fn reports_file_and_line() {
+        let missing = PINOUT.replace("Pin Name", "Name    ");
+        let err = parse(missing.as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "test.txt:4: header has no \"Pin Name\" column"
+        );
+
+        let mut binary = PINOUT.as_bytes().to_vec();
+        binary.insert(PINOUT.find("GND").unwrap() + 1, 0xff);
+        let err = parse(&binary).unwrap_err();
+        assert!(
+            matches!(err, PinoutError::Encoding { line: 5, .. }),
+            "{}",
+            err
+        );
+
+        let truncated: String = PINOUT.lines().take(4).collect::<Vec<_>>().join("\n");
+        let err = parse(truncated.as_bytes()).unwrap_err();
+        assert!(
+            matches!(err, PinoutError::EmptyTable { line: 4, .. }),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{anyhow, Context, Error};
+use regex::Regex;
+
+///KiCad pin electrical types
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ElectricalType {
+    Input,
+    Output,
+    Bidirectional,
+    TriState,
+    Passive,
+    Unspecified,
+    PowerIn,
+    PowerOut,
+    OpenCollector,
+    OpenEmitter,
+    NotConnected,
+}
+
+impl ElectricalType {
+    ///Single letter code used by the legacy `.lib` `X` record
+    pub fn legacy_code(self) -> &'static str {
+        match self {
+            ElectricalType::Input => "I",
+            ElectricalType::Output => "O",
+            ElectricalType::Bidirectional => "B",
+            ElectricalType::TriState => "T",
+            ElectricalType::Passive => "P",
+            ElectricalType::Unspecified => "U",
+            ElectricalType::PowerIn => "W",
+            ElectricalType::PowerOut => "w",
+            ElectricalType::OpenCollector => "C",
+            ElectricalType::OpenEmitter => "E",
+            ElectricalType::NotConnected => "N",
+        }
+    }
+
+    ///Keyword used by the `.kicad_sym` `pin` token
+    pub fn keyword(self) -> &'static str {
+        match self {
+            ElectricalType::Input => "input",
+            ElectricalType::Output => "output",
+            ElectricalType::Bidirectional => "bidirectional",
+            ElectricalType::TriState => "tri_state",
+            ElectricalType::Passive => "passive",
+            ElectricalType::Unspecified => "unspecified",
+            ElectricalType::PowerIn => "power_in",
+            ElectricalType::PowerOut => "power_out",
+            ElectricalType::OpenCollector => "open_collector",
+            ElectricalType::OpenEmitter => "open_emitter",
+            ElectricalType::NotConnected => "no_connect",
+        }
+    }
+}
+
+impl FromStr for ElectricalType {
+    type Err = Error;
+
+    ///Accepts the `.kicad_sym` keyword or the legacy letter code
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ALL: [ElectricalType; 11] = [
+            ElectricalType::Input,
+            ElectricalType::Output,
+            ElectricalType::Bidirectional,
+            ElectricalType::TriState,
+            ElectricalType::Passive,
+            ElectricalType::Unspecified,
+            ElectricalType::PowerIn,
+            ElectricalType::PowerOut,
+            ElectricalType::OpenCollector,
+            ElectricalType::OpenEmitter,
+            ElectricalType::NotConnected,
+        ];
+        ALL.into_iter()
+            .find(|t| t.keyword().eq_ignore_ascii_case(s) || t.legacy_code() == s)
+            .ok_or_else(|| anyhow!("unknown electrical type {:?}", s))
+    }
+}
+
+///Assigns an electrical type to pins whose name, and optionally I/O Type, match
+#[derive(Debug)]
+pub struct TypeRule {
+    ///Matched against the Xilinx pin name
+    pub name: Regex,
+    ///When set, the I/O Type column must equal this value
+    pub io_type: Option<String>,
+    pub kind: ElectricalType,
+}
+
+impl TypeRule {
+    fn new(kind: ElectricalType, name: &str, io_type: Option<&str>) -> Result<Self, Error> {
+        Ok(TypeRule {
+            name: Regex::new(name)?,
+            io_type: io_type.map(|s| s.to_string()),
+            kind,
+        })
+    }
+
+    fn matches(&self, name: &str, io_type: &str) -> bool {
+        self.io_type
+            .as_ref()
+            .is_none_or(|t| t.eq_ignore_ascii_case(io_type))
+            && self.name.is_match(name)
+    }
+}
+
+///Ordered list of classification rules, the first matching rule wins
+#[derive(Debug)]
+pub struct PinTypeTable {
+    rules: Vec<TypeRule>,
+}
+
+impl PinTypeTable {
+    ///Rules for the UltraScale+ naming scheme
+    pub fn builtin() -> Self {
+        let rules = [
+            (ElectricalType::NotConnected, r"^NC$", None),
+            (ElectricalType::PowerIn, r"^(VCC|GND)", None),
+            (
+                ElectricalType::PowerIn,
+                r"^(PS_)?MGT\w*(AVCC|AVTT|VCCAUX)",
+                None,
+            ),
+            (ElectricalType::Input, r"^(PS_)?MGT\w*REFCLK", None),
+            (ElectricalType::Input, r"^(PS_)?MGT\w*RX", None),
+            (ElectricalType::Output, r"^(PS_)?MGT\w*TX", None),
+            (ElectricalType::Bidirectional, r"^(IO_|PS_MIO)", None),
+            (ElectricalType::Bidirectional, r"", Some("HP")),
+            (ElectricalType::Bidirectional, r"", Some("HD")),
+            (ElectricalType::Bidirectional, r"", Some("HR")),
+            (ElectricalType::Bidirectional, r"", Some("PSDDR")),
+        ];
+        PinTypeTable {
+            rules: rules
+                .into_iter()
+                .map(|(kind, name, io_type)| TypeRule::new(kind, name, io_type).unwrap())
+                .collect(),
+        }
+    }
+
+    ///Read override rules from a text file.
+    ///Each line is `<type> <name regex> [I/O Type]`, `#` starts a comment.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let mut rules = Vec::new();
+        for (line_num, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if !(2..=3).contains(&parts.len()) {
+                return Err(anyhow!(
+                    "{}:{}: expected `<type> <name regex> [I/O Type]`",
+                    path.display(),
+                    line_num + 1
+                ));
+            }
+            let rule = parts[0]
+                .parse()
+                .and_then(|kind| TypeRule::new(kind, parts[1], parts.get(2).copied()))
+                .with_context(|| format!("{}:{}", path.display(), line_num + 1))?;
+            rules.push(rule);
+        }
+        Ok(PinTypeTable { rules })
+    }
+
+    ///Put the rules of `overrides` in front of the current ones
+    pub fn with_overrides(mut self, mut overrides: PinTypeTable) -> Self {
+        overrides.rules.append(&mut self.rules);
+        overrides
+    }
+
+    ///Classify a pin, falling back to passive when no rule matches
+    pub fn classify(&self, name: &str, io_type: &str) -> ElectricalType {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(name, io_type))
+            .map_or(ElectricalType::Passive, |rule| rule.kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_rules(name: &str, text: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "kicad-xilinx-symgen-{}-{}.txt",
+            name,
+            std::process::id()
+        ));
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn builtin_rules() {
+        let table = PinTypeTable::builtin();
+        let cases = [
+            ("VCCINT", "NA", ElectricalType::PowerIn),
+            ("VCCO_44", "HD", ElectricalType::PowerIn),
+            ("GND", "NA", ElectricalType::PowerIn),
+            ("NC", "NA", ElectricalType::NotConnected),
+            (
+                "IO_L1P_T0L_N0_DBC_AD7P_64",
+                "HP",
+                ElectricalType::Bidirectional,
+            ),
+            ("PS_MIO0_500", "PSMIO", ElectricalType::Bidirectional),
+            ("MGTHRXP0_128", "GTH", ElectricalType::Input),
+            ("PS_MGTRRXN3_505", "PSGTR", ElectricalType::Input),
+            ("MGTHTXN0_128", "GTH", ElectricalType::Output),
+            ("MGTREFCLK0P_128", "GTH", ElectricalType::Input),
+            ("MGTAVCC_R", "NA", ElectricalType::PowerIn),
+            ("PS_DDR_DQ0", "PSDDR", ElectricalType::Bidirectional),
+            ("DXP", "NA", ElectricalType::Passive),
+        ];
+        for (name, io_type, kind) in cases {
+            assert_eq!(table.classify(name, io_type), kind, "{}", name);
+        }
+    }
+
+    #[test]
+    fn overrides_come_first() {
+        let path = write_rules(
+            "overrides",
+            "# power rails that must stay passive\npassive ^VCCO_44$\nI ^IO_L1P HP  # legacy code\n\n",
+        );
+        let table = PinTypeTable::builtin().with_overrides(PinTypeTable::load(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(table.classify("VCCO_44", "NA"), ElectricalType::Passive);
+        assert_eq!(table.classify("VCCO_45", "NA"), ElectricalType::PowerIn);
+        assert_eq!(table.classify("IO_L1P_T0_64", "HP"), ElectricalType::Input);
+        assert_eq!(
+            table.classify("IO_L1P_T0_64", "HD"),
+            ElectricalType::Bidirectional
+        );
+    }
+
+    #[test]
+    fn load_reports_bad_lines() {
+        let cases = [
+            ("columns", "power_in\n", ":1: expected"),
+            ("type", "\nsink ^VCC\n", ":2"),
+            ("regex", "input ^MGT(\n", ":1"),
+        ];
+        for (name, text, message) in cases {
+            let path = write_rules(name, text);
+            let error = PinTypeTable::load(&path).unwrap_err();
+            fs::remove_file(&path).unwrap();
+            assert!(format!("{:#}", error).contains(message), "{:#}", error);
+        }
+        assert!(PinTypeTable::load(Path::new("/nonexistent/rules.txt")).is_err());
+    }
+}
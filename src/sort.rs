@@ -0,0 +1,139 @@
+use std::cmp::Ordering;
+
+use clap::ValueEnum;
+
+///How values are compared when sorting pins within a unit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    ///Numbers compare by value (`IO_L1N < IO_L10N`), balls by row then column
+    #[default]
+    Natural,
+    ///Plain string comparison
+    Plain,
+}
+
+impl SortOrder {
+    ///Compare two values of the column `header`
+    pub fn compare(self, header: &str, a: &str, b: &str) -> Ordering {
+        match self {
+            SortOrder::Plain => a.cmp(b),
+            SortOrder::Natural if header == "Pin" => ball_cmp(a, b),
+            SortOrder::Natural => natural_cmp(a, b),
+        }
+    }
+}
+
+///Compare BGA ball numbers, row letters first (`B < AA`), then the column
+///number (`AG2 < AG13`). Anything else falls back to [`natural_cmp`].
+pub fn ball_cmp(a: &str, b: &str) -> Ordering {
+    match (split_ball(a), split_ball(b)) {
+        (Some((row_a, col_a)), Some((row_b, col_b))) => row_a
+            .len()
+            .cmp(&row_b.len())
+            .then_with(|| row_a.cmp(row_b))
+            .then_with(|| col_a.cmp(&col_b)),
+        _ => natural_cmp(a, b),
+    }
+}
+
+///Split `AG13` into its row letters and column number
+pub fn split_ball(ball: &str) -> Option<(&str, u32)> {
+    let digits = ball.find(|c: char| c.is_ascii_digit())?;
+    let (row, col) = ball.split_at(digits);
+    if row.is_empty() || !row.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some((row, col.parse().ok()?))
+}
+
+///Compare strings treating embedded runs of digits as numbers, `G2 < G10`
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.as_bytes();
+    let mut b = b.as_bytes();
+
+    while let (Some(&ca), Some(&cb)) = (a.first(), b.first()) {
+        if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let (na, ra) = split_digits(a);
+            let (nb, rb) = split_digits(b);
+            // 去掉前导零后先比较位数，再逐位比较
+            let ta = trim_zeros(na);
+            let tb = trim_zeros(nb);
+            let ord = ta.len().cmp(&tb.len()).then_with(|| ta.cmp(tb));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            a = ra;
+            b = rb;
+        } else {
+            if ca != cb {
+                return ca.cmp(&cb);
+            }
+            a = &a[1..];
+            b = &b[1..];
+        }
+    }
+
+    a.len().cmp(&b.len())
+}
+
+fn split_digits(s: &[u8]) -> (&[u8], &[u8]) {
+    let end = s
+        .iter()
+        .position(|c| !c.is_ascii_digit())
+        .unwrap_or(s.len());
+    s.split_at(end)
+}
+
+fn trim_zeros(s: &[u8]) -> &[u8] {
+    let start = s.iter().position(|&c| c != b'0').unwrap_or(s.len());
+    &s[start..]
+}
+
+///Sort group keys, keys named in `explicit` come first in that order,
+///the rest follow in natural order
+pub fn order_keys(keys: &mut [String], explicit: &[String]) {
+    let rank = |key: &String| {
+        explicit
+            .iter()
+            .position(|e| e == key)
+            .unwrap_or(explicit.len())
+    };
+    keys.sort_by(|a, b| rank(a).cmp(&rank(b)).then_with(|| natural_cmp(a, b)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_order_of_pin_names() {
+        let mut names = vec!["IO_L10N_44", "IO_L1N_44", "IO_L2N_44", "GND", "IO_L1P_44"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec!["GND", "IO_L1N_44", "IO_L1P_44", "IO_L2N_44", "IO_L10N_44"]
+        );
+    }
+
+    #[test]
+    fn ball_order_is_row_then_column() {
+        let mut balls = vec!["AG13", "AA1", "B2", "AG2", "A10", "A9"];
+        balls.sort_by(|a, b| ball_cmp(a, b));
+        assert_eq!(balls, vec!["A9", "A10", "B2", "AA1", "AG2", "AG13"]);
+    }
+
+    #[test]
+    fn explicit_unit_order_then_natural() {
+        let mut keys: Vec<String> = ["NA", "128", "500", "44", "65", "9"]
+            .iter()
+            .map(|k| k.to_string())
+            .collect();
+        // 列出的单元按给定顺序在前，其余按自然顺序
+        let explicit = vec!["500".to_string(), "65".to_string(), "66".to_string()];
+        order_keys(&mut keys, &explicit);
+        assert_eq!(keys, vec!["500", "65", "9", "44", "128", "NA"]);
+
+        order_keys(&mut keys, &[]);
+        assert_eq!(keys, vec!["9", "44", "65", "128", "500", "NA"]);
+    }
+}
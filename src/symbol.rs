@@ -0,0 +1,569 @@
+use std::collections::HashMap;
+
+use crate::{
+    layout,
+    pinout::{PackageInfo, Pin},
+    pintype::{ElectricalType, PinTypeTable},
+};
+
+///Side of the body a pin sits on, named after the direction it points
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    ///Pin on the left edge, pointing right into the body
+    Right,
+    ///Pin on the right edge, pointing left into the body
+    Left,
+}
+
+///A pin placed in symbol coordinates (mils, Y axis up)
+#[derive(Debug)]
+pub struct SymbolPin {
+    pub name: String,
+    pub number: String,
+    ///Connection point
+    pub x: i32,
+    pub y: i32,
+    pub length: i32,
+    pub orientation: Orientation,
+    pub kind: ElectricalType,
+    ///Stacked duplicate, drawn invisible on top of a visible pin
+    pub hidden: bool,
+}
+
+///Axis aligned rectangle given by two opposite corners (mils)
+#[derive(Debug)]
+pub struct Rect {
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
+}
+
+///Free text drawn inside a unit (mils)
+#[derive(Debug)]
+pub struct Label {
+    pub text: String,
+    pub x: i32,
+    pub y: i32,
+    pub size: i32,
+}
+
+///One unit (part) of the multi-unit symbol
+#[derive(Debug)]
+pub struct Unit {
+    ///Value of the group field this unit was built from
+    pub name: String,
+    pub body: Rect,
+    pub title: Label,
+    pub pins: Vec<SymbolPin>,
+}
+
+///A complete multi-unit symbol, independent of the output format
+#[derive(Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub description: String,
+    pub keywords: String,
+    ///Extra named fields, hidden on the schematic
+    pub fields: Vec<(String, String)>,
+    pub units: Vec<Unit>,
+}
+
+impl Symbol {
+    pub fn new(name: String, units: Vec<Unit>) -> Self {
+        Symbol {
+            name,
+            description: String::new(),
+            keywords: String::new(),
+            fields: Vec::new(),
+            units,
+        }
+    }
+
+    ///Record which pinout file revision the symbol was generated from
+    pub fn with_package_info(mut self, info: &PackageInfo) -> Self {
+        let device = info.device.as_deref().unwrap_or(&self.name);
+        let mut description = format!("Xilinx {} FPGA", device);
+        if let Some(revision) = &info.revision {
+            description.push_str(&format!(", pinout revision {}", revision));
+        }
+        let details: Vec<&str> = [&info.status, &info.date]
+            .into_iter()
+            .flatten()
+            .map(|s| s.as_str())
+            .collect();
+        if !details.is_empty() {
+            description.push_str(&format!(" ({})", details.join(", ")));
+        }
+        self.description = description;
+        self.keywords = format!("FPGA Xilinx {}", device);
+
+        let fields = [
+            ("Pinout Device", &info.device),
+            ("Pinout Revision", &info.revision),
+            ("Pinout Date", &info.date),
+            ("Pinout Status", &info.status),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                self.fields.push((key.to_string(), value.clone()));
+            }
+        }
+        // 只记录最近一次修改
+        if let Some(last) = info.history.last() {
+            let mut change = format!("{} ({})", last.revision, last.date);
+            if !last.details.is_empty() {
+                change.push_str(&format!(": {}", last.details));
+            }
+            self.fields.push(("Pinout Change".to_string(), change));
+        }
+        self
+    }
+}
+
+///Vertical distance between pins (mils)
+pub const PIN_PITCH: i32 = 100;
+///Size of pin name and number texts (mils)
+pub const PIN_TEXT_SIZE: i32 = 50;
+///Gap between the body edge and the pin names (mils)
+pub const PIN_NAME_OFFSET: i32 = 40;
+///Size of the unit title (mils)
+pub const TITLE_TEXT_SIZE: i32 = 100;
+
+///Settings that control how pins are placed in a unit
+#[derive(Clone, Debug)]
+pub struct LayoutOptions {
+    ///Put power pins with the same name on one location, only the first visible
+    pub stack_power: bool,
+    ///Body width is rounded up to a multiple of this (mils)
+    pub grid: i32,
+    pub pin_length: i32,
+    ///Put in front of the unit name for its title, `Group` gives `Group44`
+    pub title_prefix: &'static str,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        LayoutOptions {
+            stack_power: false,
+            grid: 100,
+            pin_length: 150,
+            title_prefix: "Group",
+        }
+    }
+}
+
+impl LayoutOptions {
+    ///Whether pins of this kind share a row with the pins of the same name
+    pub fn stacks(&self, kind: ElectricalType) -> bool {
+        self.stack_power && matches!(kind, ElectricalType::PowerIn | ElectricalType::PowerOut)
+    }
+}
+
+///Pins that share one location, the first one is visible
+type Row<'a> = Vec<(&'a Pin, ElectricalType)>;
+
+///Classify pins and merge identically named power pins into shared rows
+fn build_rows<'a>(group: &'a [Pin], types: &PinTypeTable, options: &LayoutOptions) -> Vec<Row<'a>> {
+    let mut rows: Vec<Row> = Vec::with_capacity(group.len());
+    let mut stacks: HashMap<&str, usize> = HashMap::new();
+
+    for pin in group {
+        let kind = types.classify(&pin.name, pin.io_type.as_str());
+        if options.stacks(kind) {
+            if let Some(&row) = stacks.get(pin.name.as_str()) {
+                rows[row].push((pin, kind));
+                continue;
+            }
+            stacks.insert(&pin.name, rows.len());
+        }
+        rows.push(vec![(pin, kind)]);
+    }
+
+    rows
+}
+
+///Rows that must stay together in one column, a differential pair is P over N
+type Block<'a> = Vec<Row<'a>>;
+
+///Join the two legs of each differential pair into one block, placed where
+///the first leg appeared
+fn build_blocks(rows: Vec<Row>) -> Vec<Block> {
+    let mut legs: HashMap<String, [Option<usize>; 2]> = HashMap::new();
+    for (i, row) in rows.iter().enumerate() {
+        if let [(pin, _)] = row.as_slice() {
+            if let Some((key, polarity)) = layout::diff_pair(&pin.name) {
+                let leg = &mut legs.entry(key).or_default()[polarity as usize];
+                // 同名重复时不配对
+                *leg = if leg.is_none() {
+                    Some(i)
+                } else {
+                    Some(usize::MAX)
+                };
+            }
+        }
+    }
+
+    let mut partner = vec![None; rows.len()];
+    for [p, n] in legs.into_values() {
+        if let (Some(p), Some(n)) = (p, n) {
+            if p != usize::MAX && n != usize::MAX {
+                partner[p] = Some((p, n));
+                partner[n] = Some((p, n));
+            }
+        }
+    }
+
+    let mut rows: Vec<Option<Row>> = rows.into_iter().map(Some).collect();
+    let mut blocks = Vec::with_capacity(rows.len());
+    for i in 0..rows.len() {
+        let Some(row) = rows[i].take() else {
+            continue;
+        };
+        match partner[i] {
+            Some((p, n)) if p == i => blocks.push(vec![row, rows[n].take().unwrap()]),
+            Some((p, _)) => blocks.push(vec![rows[p].take().unwrap(), row]),
+            None => blocks.push(vec![row]),
+        }
+    }
+    blocks
+}
+
+///Snap `value` up to the next multiple of `grid`
+fn snap_up(value: i32, grid: i32) -> i32 {
+    if grid <= 0 {
+        return value;
+    }
+    (value + grid - 1).div_euclid(grid) * grid
+}
+
+///Rough width of a text in the default KiCad font
+fn text_width(text: &str, size: i32) -> i32 {
+    text.chars().count() as i32 * size
+}
+
+impl Unit {
+    ///Lay out a group of pins in two balanced columns, the left one taking the
+    ///extra row for odd counts. Differential pairs stay together with P above
+    ///N. The body is sized to fit the longest pin names on either side and the
+    ///taller column.
+    pub fn from_group(
+        name: &str,
+        group: &[Pin],
+        types: &PinTypeTable,
+        options: &LayoutOptions,
+    ) -> Self {
+        let blocks = build_blocks(build_rows(group, types, options));
+        let heights: Vec<usize> = blocks.iter().map(|block| block.len()).collect();
+        let half = layout::split_columns(&heights);
+        let (left_height, right_height) = layout::column_heights(&heights, half);
+        let tallest = left_height.max(right_height).max(1) as i32;
+        let title = format!("{}{}", options.title_prefix, name);
+
+        let widest = |blocks: &[Block]| {
+            blocks
+                .iter()
+                .flatten()
+                .flatten()
+                .map(|(pin, _)| text_width(&pin.name, PIN_TEXT_SIZE))
+                .max()
+                .unwrap_or(0)
+        };
+        let (left_blocks, right_blocks) = blocks.split_at(half);
+        let names = PIN_NAME_OFFSET * 2 + widest(left_blocks) + widest(right_blocks);
+        let width = snap_up(
+            (names + options.grid)
+                .max(text_width(&title, TITLE_TEXT_SIZE))
+                .max(options.grid * 4),
+            options.grid,
+        );
+
+        let x1 = options.pin_length;
+        let x2 = x1 + width;
+        let mut pins = Vec::with_capacity(group.len());
+
+        let rows = blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(i, block)| block.iter().map(move |row| (i < half, row)));
+        let mut next_row = [0, 0];
+        for (left, row) in rows {
+            let row_index = next_row[left as usize];
+            next_row[left as usize] += 1;
+            for (j, (pin, kind)) in row.iter().enumerate() {
+                pins.push(SymbolPin {
+                    name: pin.name.clone(),
+                    number: pin.number.clone(),
+                    x: if left { 0 } else { x2 + options.pin_length },
+                    y: -row_index * PIN_PITCH,
+                    length: options.pin_length,
+                    orientation: if left {
+                        Orientation::Right
+                    } else {
+                        Orientation::Left
+                    },
+                    // 隐藏的 power_in 会生成隐式全局网络，堆叠的副本改为 passive
+                    kind: if j == 0 {
+                        *kind
+                    } else {
+                        ElectricalType::Passive
+                    },
+                    hidden: j > 0,
+                });
+            }
+        }
+
+        Unit {
+            name: name.to_string(),
+            body: Rect {
+                x1,
+                y1: PIN_PITCH,
+                x2,
+                y2: -tallest * PIN_PITCH,
+            },
+            title: Label {
+                text: title,
+                x: (x1 + x2) / 2,
+                y: PIN_PITCH + TITLE_TEXT_SIZE,
+                size: TITLE_TEXT_SIZE,
+            },
+            pins,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinout::{IoType, Revision};
+
+    fn group(count: usize) -> Vec<Pin> {
+        (0..count)
+            .map(|i| {
+                Pin::test(
+                    &format!("A{}", i + 1),
+                    &format!("IO_L{}P_44", i + 1),
+                    Some(44),
+                    IoType::Hp,
+                )
+            })
+            .collect()
+    }
+
+    fn layout(count: usize) -> Unit {
+        Unit::from_group(
+            "44",
+            &group(count),
+            &PinTypeTable::builtin(),
+            &LayoutOptions::default(),
+        )
+    }
+
+    fn column(unit: &Unit, orientation: Orientation) -> Vec<i32> {
+        unit.pins
+            .iter()
+            .filter(|p| p.orientation == orientation)
+            .map(|p| p.y)
+            .collect()
+    }
+
+    fn assert_inside_body(unit: &Unit) {
+        for pin in &unit.pins {
+            assert!(
+                pin.y < unit.body.y1 && pin.y > unit.body.y2,
+                "{} at y={} outside body {:?}",
+                pin.name,
+                pin.y,
+                unit.body
+            );
+        }
+    }
+
+    #[test]
+    fn package_info_goes_into_fields() {
+        let info = PackageInfo {
+            device: Some("xczu15egffvb1156".to_string()),
+            revision: Some("1.1".to_string()),
+            status: Some("Production".to_string()),
+            history: vec![
+                Revision {
+                    revision: "1.0".to_string(),
+                    date: "10/6/2016".to_string(),
+                    ..Default::default()
+                },
+                Revision {
+                    revision: "1.1".to_string(),
+                    date: "4/25/2017".to_string(),
+                    status: "Production".to_string(),
+                    details: "Updated XCZU15EG to Production".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        let symbol = Symbol::new("xczu15eg".to_string(), Vec::new()).with_package_info(&info);
+        assert_eq!(
+            symbol.description,
+            "Xilinx xczu15egffvb1156 FPGA, pinout revision 1.1 (Production)"
+        );
+        assert_eq!(
+            symbol.fields.last().unwrap(),
+            &(
+                "Pinout Change".to_string(),
+                "1.1 (4/25/2017): Updated XCZU15EG to Production".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn odd_group_puts_extra_pin_on_the_left() {
+        let unit = layout(7);
+        assert_eq!(column(&unit, Orientation::Right), vec![0, -100, -200, -300]);
+        assert_eq!(column(&unit, Orientation::Left), vec![0, -100, -200]);
+        assert_eq!(unit.body.y2, -400);
+        assert_inside_body(&unit);
+    }
+
+    #[test]
+    fn even_group_splits_in_half() {
+        let unit = layout(8);
+        assert_eq!(column(&unit, Orientation::Right).len(), 4);
+        assert_eq!(column(&unit, Orientation::Left).len(), 4);
+        assert_eq!(unit.body.y2, -400);
+        assert_inside_body(&unit);
+    }
+
+    #[test]
+    fn single_pin_group() {
+        let unit = layout(1);
+        assert_eq!(column(&unit, Orientation::Right), vec![0]);
+        assert!(column(&unit, Orientation::Left).is_empty());
+        assert_inside_body(&unit);
+    }
+
+    #[test]
+    fn right_pins_touch_the_body() {
+        let unit = layout(5);
+        for pin in &unit.pins {
+            let end = match pin.orientation {
+                Orientation::Right => pin.x + pin.length,
+                Orientation::Left => pin.x - pin.length,
+            };
+            let edge = match pin.orientation {
+                Orientation::Right => unit.body.x1,
+                Orientation::Left => unit.body.x2,
+            };
+            assert_eq!(end, edge, "{}", pin.name);
+        }
+    }
+
+    fn power(names: &[&str], stack_power: bool) -> Unit {
+        let pins: Vec<Pin> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Pin::test(&format!("B{}", i + 1), name, None, IoType::Na))
+            .collect();
+        let options = LayoutOptions {
+            stack_power,
+            ..Default::default()
+        };
+        Unit::from_group("GND", &pins, &PinTypeTable::builtin(), &options)
+    }
+
+    #[test]
+    fn stacks_power_pins_by_name() {
+        let unit = power(&["GND", "VCCINT", "GND", "GND", "VCCINT"], true);
+        let gnd: Vec<&SymbolPin> = unit.pins.iter().filter(|p| p.name == "GND").collect();
+        assert_eq!(gnd.len(), 3);
+
+        // 第一个保持可见，其余隐藏并改为 passive，且位置相同
+        assert_eq!(gnd[0].number, "B1");
+        assert!(!gnd[0].hidden);
+        assert_eq!(gnd[0].kind, ElectricalType::PowerIn);
+        for copy in &gnd[1..] {
+            assert!(copy.hidden);
+            assert_eq!(copy.kind, ElectricalType::Passive);
+            assert_eq!((copy.x, copy.y), (gnd[0].x, gnd[0].y));
+        }
+
+        // 两行分在两列，主体只有一行高
+        assert_eq!(unit.body.y2, -PIN_PITCH);
+        let unstacked = power(&["GND", "VCCINT", "GND", "GND", "VCCINT"], false);
+        assert!(unstacked.pins.iter().all(|p| !p.hidden));
+        assert_eq!(unstacked.body.y2, -3 * PIN_PITCH);
+    }
+
+    fn sized(name: &str, pins: &[&str], options: &LayoutOptions) -> Unit {
+        let pins: Vec<Pin> = pins
+            .iter()
+            .enumerate()
+            .map(|(i, pin)| Pin::test(&format!("C{}", i + 1), pin, None, IoType::Na))
+            .collect();
+        Unit::from_group(name, &pins, &PinTypeTable::builtin(), options)
+    }
+
+    fn width(unit: &Unit) -> i32 {
+        unit.body.x2 - unit.body.x1
+    }
+
+    #[test]
+    fn body_width_follows_the_longest_names() {
+        let options = LayoutOptions::default();
+        // 一左一右：2 * 40 + 6 * 50 + 11 * 50 + 100 = 1030，取整到 1100
+        let unit = sized("0", &["VCCINT", "PS_MGTRAVCC"], &options);
+        assert_eq!(column(&unit, Orientation::Right).len(), 1);
+        assert_eq!(width(&unit), 1100);
+
+        // 左右两列各自只算最长的名字
+        let unit = sized(
+            "0",
+            &["VCCINT_LONGER_NAME", "A", "PS_MGTRAVCC", "B"],
+            &options,
+        );
+        assert_eq!(width(&unit), 1700);
+    }
+
+    #[test]
+    fn body_width_snaps_to_the_grid() {
+        // 名字占 930，再加一格余量后向上取整
+        for (grid, expected) in [(50, 1000), (100, 1100), (250, 1250)] {
+            let options = LayoutOptions {
+                grid,
+                ..Default::default()
+            };
+            let unit = sized("0", &["VCCINT", "PS_MGTRAVCC"], &options);
+            assert_eq!(width(&unit), expected, "grid {}", grid);
+        }
+    }
+
+    #[test]
+    fn title_sets_the_minimum_width() {
+        // 引脚名只需要 230，最小宽度为四格
+        let options = LayoutOptions {
+            title_prefix: "",
+            ..Default::default()
+        };
+        assert_eq!(width(&sized("IO", &["A"], &options)), 400);
+        assert_eq!(
+            width(&sized("PS_MIO_BANK_500_501_502", &["A"], &options)),
+            2300
+        );
+        // 前缀也算在标题里
+        assert_eq!(width(&sized("44", &["A"], &LayoutOptions::default())), 700);
+    }
+
+    #[test]
+    fn title_is_centred_over_the_body() {
+        let options = LayoutOptions {
+            pin_length: 100,
+            ..Default::default()
+        };
+        for unit in [
+            sized("0", &["VCCINT", "PS_MGTRAVCC"], &options),
+            sized("PS_MIO_BANK_500_501_502", &["A"], &options),
+        ] {
+            assert_eq!(unit.body.x1, 100);
+            assert_eq!(unit.title.x, (unit.body.x1 + unit.body.x2) / 2);
+            assert_eq!(unit.title.y, unit.body.y1 + TITLE_TEXT_SIZE);
+        }
+    }
+}
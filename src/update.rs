@@ -0,0 +1,314 @@
+use anyhow::{bail, Error};
+
+use crate::{
+    symbol::Symbol,
+    writer::{self, Format},
+};
+
+///Result of splicing generated symbols into an existing library
+#[derive(Debug, Default)]
+pub struct Update {
+    pub text: String,
+    ///Symbols that replaced one of the same name
+    pub replaced: Vec<String>,
+    ///Symbols appended to the library
+    pub inserted: Vec<String>,
+}
+
+///Replace the symbols of the same name in `existing` or add them, everything
+///else in the library is kept byte for byte
+pub fn update_library(format: Format, existing: &str, symbols: &[Symbol]) -> Result<Update, Error> {
+    match format {
+        Format::Legacy => {
+            if !existing.starts_with("EESchema-LIBRARY") {
+                bail!("not a legacy KiCad symbol library");
+            }
+            let blocks = Blocks {
+                start: "DEF",
+                end: "ENDDEF",
+                footer: "#End Library",
+            };
+            blocks.splice(existing, symbols, writer::write_legacy_symbol)
+        }
+        Format::KicadSym => update_kicad_sym(existing, symbols),
+    }
+}
+
+///Same as [`update_library`] for the `.dcm` file of a legacy library
+pub fn update_legacy_dcm(existing: &str, symbols: &[Symbol]) -> Result<Update, Error> {
+    if !existing.starts_with("EESchema-DOCLIB") {
+        bail!("not a legacy KiCad documentation library");
+    }
+    let blocks = Blocks {
+        start: "$CMP",
+        end: "$ENDCMP",
+        footer: "#End Doc Library",
+    };
+    blocks.splice(existing, symbols, writer::write_legacy_dcm_entry)
+}
+
+///Name and byte range of a symbol in a library file
+type Entry = (String, usize, usize);
+
+///Line based `<start> name` ... `<end>` entries of the legacy formats
+struct Blocks {
+    start: &'static str,
+    end: &'static str,
+    footer: &'static str,
+}
+
+impl Blocks {
+    ///Byte range and name of every entry, plus the insert position for new
+    ///ones: after the last entry, or before the footer
+    fn scan(&self, text: &str) -> Result<(Vec<Entry>, usize), Error> {
+        let mut entries = Vec::new();
+        let mut open: Option<(String, usize)> = None;
+        let mut footer = None;
+        let mut separator = None;
+        let mut offset = 0;
+
+        for line in text.split_inclusive('\n') {
+            let trimmed = line.trim();
+            let mut words = trimmed.split_whitespace();
+            let keyword = words.next();
+            if keyword == Some(self.start) {
+                // 旧格式中 ~ 前缀表示 Value 字段隐藏，不属于名称
+                let name = words.next().unwrap_or_default().trim_start_matches('~');
+                open = Some((name.to_string(), offset));
+            } else if keyword == Some(self.end) {
+                if let Some((name, start)) = open.take() {
+                    entries.push((name, start, offset + line.len()));
+                }
+            } else if trimmed.starts_with(self.footer) {
+                footer = Some(separator.unwrap_or(offset));
+                break;
+            }
+            separator = (trimmed == "#").then(|| separator.unwrap_or(offset));
+            offset += line.len();
+        }
+
+        let Some(footer) = footer else {
+            bail!("missing {:?} line", self.footer);
+        };
+        let insert = entries.last().map_or(footer, |e| e.2);
+        Ok((entries, insert))
+    }
+
+    fn splice(
+        &self,
+        existing: &str,
+        symbols: &[Symbol],
+        render: fn(&Symbol) -> String,
+    ) -> Result<Update, Error> {
+        let (entries, insert) = self.scan(existing)?;
+        let mut update = Update::default();
+        let mut edits: Vec<(usize, usize, String)> = Vec::new();
+        let mut appended = String::new();
+
+        for symbol in symbols {
+            let block = render(symbol);
+            match entries.iter().find(|e| e.0 == symbol.name) {
+                Some(&(_, start, end)) => {
+                    // 只替换条目本身，保留前面的注释
+                    let body = &block[block.find(self.start).unwrap_or(0)..];
+                    edits.push((start, end, body.to_string()));
+                    update.replaced.push(symbol.name.clone());
+                }
+                None => {
+                    appended.push_str(&block);
+                    update.inserted.push(symbol.name.clone());
+                }
+            }
+        }
+        edits.push((insert, insert, appended));
+        update.text = apply(existing, edits);
+        Ok(update)
+    }
+}
+
+///Apply non-overlapping `(start, end, replacement)` edits
+fn apply(text: &str, mut edits: Vec<(usize, usize, String)>) -> String {
+    edits.sort_by_key(|e| (e.0, e.1));
+    let mut out = String::with_capacity(text.len());
+    let mut pos = 0;
+    for (start, end, replacement) in edits {
+        out.push_str(&text[pos..start]);
+        out.push_str(&replacement);
+        pos = end;
+    }
+    out.push_str(&text[pos..]);
+    out
+}
+
+fn update_kicad_sym(existing: &str, symbols: &[Symbol]) -> Result<Update, Error> {
+    let (children, root_end) = scan_kicad_sym(existing)?;
+    let mut update = Update::default();
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    let mut appended = String::new();
+
+    for symbol in symbols {
+        let block = writer::write_kicad_sym_symbol(symbol);
+        match children.iter().find(|c| c.0 == symbol.name) {
+            Some(&(_, start, end)) => {
+                edits.push((start, end, block));
+                update.replaced.push(symbol.name.clone());
+            }
+            None => {
+                appended.push_str(&block);
+                update.inserted.push(symbol.name.clone());
+            }
+        }
+    }
+    edits.push((root_end, root_end, appended));
+    update.text = apply(existing, edits);
+    Ok(update)
+}
+
+///Top level `(symbol "name" ...)` entries of a `.kicad_sym` file as name and
+///byte range of their lines, plus the start of the line closing the library
+fn scan_kicad_sym(text: &str) -> Result<(Vec<Entry>, usize), Error> {
+    let bytes = text.as_bytes();
+    // 条目所在行的行首，以及条目结束后换行符之后的位置
+    let line_start = |pos: usize| {
+        let start = text[..pos].rfind('\n').map_or(0, |i| i + 1);
+        if text[start..pos].trim().is_empty() {
+            start
+        } else {
+            pos
+        }
+    };
+    let line_end = |pos: usize| {
+        let rest = &text[pos..];
+        match rest.find('\n') {
+            Some(i) if rest[..i].trim().is_empty() => pos + i + 1,
+            _ => pos,
+        }
+    };
+
+    let mut children = Vec::new();
+    let mut depth = 0;
+    let mut child: Option<(Option<String>, usize)> = None;
+    let mut root_seen = false;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let (_, next) = read_string(text, i)?;
+                i = next;
+                continue;
+            }
+            b'(' => {
+                depth += 1;
+                if depth == 1 {
+                    if root_seen || !text[i + 1..].starts_with("kicad_symbol_lib") {
+                        bail!("not a KiCad symbol library");
+                    }
+                    root_seen = true;
+                } else if depth == 2 {
+                    child = Some((symbol_name(text, i + 1)?, i));
+                }
+            }
+            b')' => {
+                if depth == 0 {
+                    bail!("unbalanced ')'");
+                }
+                if depth == 2 {
+                    if let Some((Some(name), start)) = child.take() {
+                        children.push((name, line_start(start), line_end(i + 1)));
+                    }
+                } else if depth == 1 {
+                    return Ok((children, line_start(i)));
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    bail!("unexpected end of file")
+}
+
+///Name of a `(symbol "name"` list starting at `pos`, None for other lists
+fn symbol_name(text: &str, pos: usize) -> Result<Option<String>, Error> {
+    let Some(rest) = text[pos..].strip_prefix("symbol") else {
+        return Ok(None);
+    };
+    let skipped = rest.len() - rest.trim_start().len();
+    if skipped == 0 {
+        return Ok(None);
+    }
+    let start = pos + "symbol".len() + skipped;
+    if text[start..].starts_with('"') {
+        return Ok(Some(read_string(text, start)?.0));
+    }
+    let end = text[start..]
+        .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .map_or(text.len(), |i| start + i);
+    Ok(Some(text[start..end].to_string()))
+}
+
+///Unescaped contents of the quoted string at `start` and the position after it
+fn read_string(text: &str, start: usize) -> Result<(String, usize), Error> {
+    let mut value = String::new();
+    let mut chars = text[start + 1..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, start + 1 + i + 1)),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, c)) => value.push(c),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    bail!("unterminated string")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols(names: &[&str]) -> Vec<Symbol> {
+        names
+            .iter()
+            .map(|name| Symbol::new(name.to_string(), Vec::new()))
+            .collect()
+    }
+
+    #[test]
+    fn legacy_keeps_other_symbols() {
+        let existing = "EESchema-LIBRARY Version 2.4\n#encoding utf-8\n#\n# MINE\n#\nDEF MINE U 0 40 Y Y 1 F N\nF0 \"U\" 0 0 50 H V C CNN\nENDDEF\n#\n# xc7a35t\n#\nDEF ~xc7a35t U 0 40 Y Y 1 L N\nX OLD 1 0 0 100 R 50 50 1 1 P\nENDDEF\n#\n#End Library\n";
+        let update =
+            update_library(Format::Legacy, existing, &symbols(&["xc7a35t", "xc7a50t"])).unwrap();
+
+        assert_eq!(update.replaced, vec!["xc7a35t"]);
+        assert_eq!(update.inserted, vec!["xc7a50t"]);
+        assert!(update
+            .text
+            .contains("DEF MINE U 0 40 Y Y 1 F N\nF0 \"U\" 0 0 50 H V C CNN\nENDDEF\n"));
+        assert!(!update.text.contains("X OLD"));
+        assert!(update.text.contains("# xc7a35t\n#\nDEF xc7a35t "));
+        assert!(update.text.ends_with("ENDDEF\n#\n#End Library\n"));
+        assert_eq!(update.text.matches("DEF ").count(), 3);
+    }
+
+    #[test]
+    fn kicad_sym_keeps_other_symbols() {
+        let existing = "(kicad_symbol_lib (version 20231120) (generator \"kicad_symbol_editor\")\n  (symbol \"MINE\" (in_bom yes)\n    (property \"Reference\" \"U\" (at 0 0 0))\n  )\n  (symbol \"xc7a35t\"\n    (property \"Value\" \"(old)\" (at 0 0 0))\n  )\n)\n";
+        let update = update_library(
+            Format::KicadSym,
+            existing,
+            &symbols(&["xc7a35t", "xc7a50t"]),
+        )
+        .unwrap();
+
+        assert_eq!(update.replaced, vec!["xc7a35t"]);
+        assert_eq!(update.inserted, vec!["xc7a50t"]);
+        assert!(update.text.starts_with("(kicad_symbol_lib (version 20231120) (generator \"kicad_symbol_editor\")\n  (symbol \"MINE\" (in_bom yes)\n    (property \"Reference\" \"U\" (at 0 0 0))\n  )\n  (symbol \"xc7a35t\"\n    (pin_names"));
+        assert!(!update.text.contains("(old)"));
+        assert!(update.text.contains("  )\n  (symbol \"xc7a50t\"\n"));
+        assert!(update.text.ends_with("  )\n)\n"));
+    }
+}
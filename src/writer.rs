@@ -0,0 +1,395 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::symbol::{Orientation, Symbol, PIN_NAME_OFFSET, PIN_TEXT_SIZE};
+
+///Output library formats
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    ///Legacy `EESchema-LIBRARY Version 2.4` (.lib), KiCad 5 and older
+    Legacy,
+    ///S-expression symbol library (.kicad_sym), KiCad 8 and newer
+    KicadSym,
+}
+
+impl Format {
+    ///Guess the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "lib" => Some(Format::Legacy),
+            "kicad_sym" => Some(Format::KicadSym),
+            _ => None,
+        }
+    }
+
+    ///File extension conventionally used for this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Legacy => "lib",
+            Format::KicadSym => "kicad_sym",
+        }
+    }
+
+    ///Render symbols into a complete library file
+    pub fn render(self, symbols: &[Symbol]) -> String {
+        match self {
+            Format::Legacy => write_legacy(symbols),
+            Format::KicadSym => write_kicad_sym(symbols),
+        }
+    }
+
+    ///Companion documentation file, legacy libraries keep descriptions in a `.dcm`
+    pub fn render_doc(self, symbols: &[Symbol]) -> Option<String> {
+        match self {
+            Format::Legacy => Some(write_legacy_dcm(symbols)),
+            Format::KicadSym => None,
+        }
+    }
+}
+
+///Render the symbols as a legacy `.lib` library
+pub fn write_legacy(symbols: &[Symbol]) -> String {
+    let mut lib = String::new();
+    lib.push_str("EESchema-LIBRARY Version 2.4\n#encoding utf-8\n");
+    for symbol in symbols {
+        lib.push_str(&write_legacy_symbol(symbol));
+    }
+    lib.push_str("#\n#End Library\n");
+    lib
+}
+
+///`DEF` ... `ENDDEF` block of one symbol in a legacy library
+pub fn write_legacy_symbol(symbol: &Symbol) -> String {
+    let mut lib = String::new();
+    lib.push_str(&format!("#\n# {}\n#\n", symbol.name));
+    lib.push_str(&format!(
+        "DEF {} U 0 {} Y Y {} L N\n",
+        symbol.name,
+        PIN_NAME_OFFSET,
+        symbol.units.len()
+    ));
+    lib.push_str("F0 \"U\" 0 300 50 H V C CNN\n");
+    lib.push_str("F1 \"FPGA\" 0 200 50 H V C CNN\n");
+    lib.push_str("F2 \"\" 0 0 50 H I C CNN\n");
+    lib.push_str("F3 \"\" 0 0 50 H I C CNN\n");
+    for (i, (key, value)) in symbol.fields.iter().enumerate() {
+        lib.push_str(&format!(
+            "F{} \"{}\" 0 {} 50 H I L CNN \"{}\"\n",
+            i + 4,
+            value,
+            -100 * (i as i32 + 1),
+            key
+        ));
+    }
+    lib.push_str("DRAW\n");
+
+    for (unit_number, unit) in (1..).zip(symbol.units.iter()) {
+        for pin in &unit.pins {
+            let orientation = match pin.orientation {
+                Orientation::Right => "R",
+                Orientation::Left => "L",
+            };
+            // 形状字段 N 表示隐藏引脚
+            let shape = if pin.hidden { " N" } else { "" };
+            lib.push_str(&format!(
+                "X {} {} {} {} {} {} {} {} {} 1 {}{}\n",
+                pin.name,
+                pin.number,
+                pin.x,
+                pin.y,
+                pin.length,
+                orientation,
+                PIN_TEXT_SIZE,
+                PIN_TEXT_SIZE,
+                unit_number,
+                pin.kind.legacy_code(),
+                shape
+            ));
+        }
+
+        let body = &unit.body;
+        lib.push_str(&format!(
+            "S {} {} {} {} {} 1 0 f\n",
+            body.x1, body.y1, body.x2, body.y2, unit_number
+        ));
+        let title = &unit.title;
+        lib.push_str(&format!(
+            "T 0 {} {} {} 0 {} 1 {} Normal 0 C C\n",
+            title.x,
+            title.y,
+            title.size,
+            unit_number,
+            // 旧格式用 ~ 表示空格
+            title.text.replace(' ', "~")
+        ));
+    }
+
+    lib.push_str("ENDDRAW\n");
+    lib.push_str("ENDDEF\n");
+    lib
+}
+
+///Render the legacy `.dcm` documentation library for the symbols
+pub fn write_legacy_dcm(symbols: &[Symbol]) -> String {
+    let mut dcm = String::new();
+    dcm.push_str("EESchema-DOCLIB  Version 2.0\n");
+    for symbol in symbols {
+        dcm.push_str(&write_legacy_dcm_entry(symbol));
+    }
+    dcm.push_str("#\n#End Doc Library\n");
+    dcm
+}
+
+///`$CMP` ... `$ENDCMP` entry of one symbol in a `.dcm` file
+pub fn write_legacy_dcm_entry(symbol: &Symbol) -> String {
+    let mut dcm = String::new();
+    dcm.push_str(&format!("#\n$CMP {}\n", symbol.name));
+    if !symbol.description.is_empty() {
+        dcm.push_str(&format!("D {}\n", symbol.description));
+    }
+    if !symbol.keywords.is_empty() {
+        dcm.push_str(&format!("K {}\n", symbol.keywords));
+    }
+    dcm.push_str("$ENDCMP\n");
+    dcm
+}
+
+///Render the symbols as a KiCad 8 `.kicad_sym` library
+pub fn write_kicad_sym(symbols: &[Symbol]) -> String {
+    let mut lib = String::new();
+    lib.push_str("(kicad_symbol_lib\n");
+    lib.push_str("  (version 20231120)\n");
+    lib.push_str("  (generator \"kicad-xilinx-symgen\")\n");
+    for symbol in symbols {
+        lib.push_str(&write_kicad_sym_symbol(symbol));
+    }
+    lib.push_str(")\n");
+    lib
+}
+
+///Top level `(symbol ...)` of one symbol in a `.kicad_sym` library
+pub fn write_kicad_sym_symbol(symbol: &Symbol) -> String {
+    let mut lib = String::new();
+    let name = quote(&symbol.name);
+    lib.push_str(&format!("  (symbol {}\n", name));
+    lib.push_str(&format!(
+        "    (pin_names (offset {}))\n",
+        mm(PIN_NAME_OFFSET)
+    ));
+    lib.push_str("    (exclude_from_sim no)\n");
+    lib.push_str("    (in_bom yes)\n");
+    lib.push_str("    (on_board yes)\n");
+    push_property(&mut lib, "Reference", "U", 0, 300, false);
+    push_property(&mut lib, "Value", "FPGA", 0, 200, false);
+    push_property(&mut lib, "Footprint", "", 0, 0, true);
+    push_property(&mut lib, "Datasheet", "", 0, 0, true);
+    push_property(&mut lib, "Description", &symbol.description, 0, 0, true);
+    push_property(&mut lib, "ki_keywords", &symbol.keywords, 0, 0, true);
+    for (i, (key, value)) in symbol.fields.iter().enumerate() {
+        push_property(&mut lib, key, value, 0, -100 * (i as i32 + 1), true);
+    }
+
+    for (unit_number, unit) in (1..).zip(symbol.units.iter()) {
+        let unit_symbol = quote(&format!("{}_{}_1", symbol.name, unit_number));
+        lib.push_str(&format!("    (symbol {}\n", unit_symbol));
+        lib.push_str(&format!("      (unit_name {})\n", quote(&unit.name)));
+
+        let body = &unit.body;
+        lib.push_str(&format!(
+            "      (rectangle (start {} {}) (end {} {})\n        (stroke (width 0) (type default))\n        (fill (type background))\n      )\n",
+            mm(body.x1),
+            mm(body.y1),
+            mm(body.x2),
+            mm(body.y2)
+        ));
+
+        let title = &unit.title;
+        lib.push_str(&format!(
+            "      (text {} (at {} {} 0)\n        (effects (font (size {} {})))\n      )\n",
+            quote(&title.text),
+            mm(title.x),
+            mm(title.y),
+            mm(title.size),
+            mm(title.size)
+        ));
+
+        for pin in &unit.pins {
+            let angle = match pin.orientation {
+                Orientation::Right => 0,
+                Orientation::Left => 180,
+            };
+            lib.push_str(&format!(
+                "      (pin {} line (at {} {} {}) (length {}){}\n        (name {} (effects (font (size {} {}))))\n        (number {} (effects (font (size {} {}))))\n      )\n",
+                pin.kind.keyword(),
+                mm(pin.x),
+                mm(pin.y),
+                angle,
+                mm(pin.length),
+                if pin.hidden { " hide" } else { "" },
+                quote(&pin.name),
+                mm(PIN_TEXT_SIZE),
+                mm(PIN_TEXT_SIZE),
+                quote(&pin.number),
+                mm(PIN_TEXT_SIZE),
+                mm(PIN_TEXT_SIZE)
+            ));
+        }
+
+        lib.push_str("    )\n");
+    }
+
+    lib.push_str("  )\n");
+    lib
+}
+
+fn push_property(lib: &mut String, key: &str, value: &str, x: i32, y: i32, hidden: bool) {
+    lib.push_str(&format!(
+        "    (property {} {} (at {} {} 0)\n      (effects (font (size 1.27 1.27)){})\n    )\n",
+        quote(key),
+        quote(value),
+        mm(x),
+        mm(y),
+        if hidden { " hide" } else { "" }
+    ));
+}
+
+///Convert mils to a millimetre literal without float noise
+fn mm(mils: i32) -> String {
+    // 1 mil = 0.0254 mm, keep four decimals as an integer
+    let tenths_of_um = mils as i64 * 254;
+    let sign = if tenths_of_um < 0 { "-" } else { "" };
+    let abs = tenths_of_um.abs();
+    let frac = format!("{:04}", abs % 10000);
+    let frac = frac.trim_end_matches('0');
+    if frac.is_empty() {
+        format!("{}{}", sign, abs / 10000)
+    } else {
+        format!("{}{}.{}", sign, abs / 10000, frac)
+    }
+}
+
+///Quote and escape a string for an S-expression
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pintype::ElectricalType,
+        symbol::{Label, Rect, SymbolPin, Unit},
+    };
+
+    #[test]
+    fn converts_mils_to_mm() {
+        assert_eq!(mm(0), "0");
+        assert_eq!(mm(100), "2.54");
+        assert_eq!(mm(50), "1.27");
+        assert_eq!(mm(-150), "-3.81");
+        assert_eq!(mm(1), "0.0254");
+        assert_eq!(mm(1000), "25.4");
+    }
+
+    #[test]
+    fn quotes_and_escapes() {
+        assert_eq!(quote("IO_L1P_64"), "\"IO_L1P_64\"");
+        assert_eq!(quote(""), "\"\"");
+        assert_eq!(quote("a \"b\"\\c\nd"), "\"a \\\"b\\\"\\\\c\\nd\"");
+    }
+
+    #[test]
+    fn kicad_sym_single_unit() {
+        let unit = Unit {
+            name: "Bank 0".to_string(),
+            body: Rect {
+                x1: 150,
+                y1: 100,
+                x2: 750,
+                y2: -100,
+            },
+            title: Label {
+                text: "Bank 0".to_string(),
+                x: 450,
+                y: 200,
+                size: 100,
+            },
+            pins: vec![SymbolPin {
+                name: "DONE_0".to_string(),
+                number: "AD8".to_string(),
+                x: 0,
+                y: 0,
+                length: 150,
+                orientation: Orientation::Right,
+                kind: ElectricalType::Bidirectional,
+                hidden: false,
+            }],
+        };
+        let mut symbol = Symbol::new("xczu2cg".to_string(), vec![unit]);
+        symbol.description = "Xilinx xczu2cg FPGA".to_string();
+        symbol.keywords = "FPGA".to_string();
+        symbol
+            .fields
+            .push(("Pinout Revision".to_string(), "1.0".to_string()));
+
+        let expected = r#"(kicad_symbol_lib
+  (version 20231120)
+  (generator "kicad-xilinx-symgen")
+  (symbol "xczu2cg"
+    (pin_names (offset 1.016))
+    (exclude_from_sim no)
+    (in_bom yes)
+    (on_board yes)
+    (property "Reference" "U" (at 0 7.62 0)
+      (effects (font (size 1.27 1.27)))
+    )
+    (property "Value" "FPGA" (at 0 5.08 0)
+      (effects (font (size 1.27 1.27)))
+    )
+    (property "Footprint" "" (at 0 0 0)
+      (effects (font (size 1.27 1.27)) hide)
+    )
+    (property "Datasheet" "" (at 0 0 0)
+      (effects (font (size 1.27 1.27)) hide)
+    )
+    (property "Description" "Xilinx xczu2cg FPGA" (at 0 0 0)
+      (effects (font (size 1.27 1.27)) hide)
+    )
+    (property "ki_keywords" "FPGA" (at 0 0 0)
+      (effects (font (size 1.27 1.27)) hide)
+    )
+    (property "Pinout Revision" "1.0" (at 0 -2.54 0)
+      (effects (font (size 1.27 1.27)) hide)
+    )
+    (symbol "xczu2cg_1_1"
+      (unit_name "Bank 0")
+      (rectangle (start 3.81 2.54) (end 19.05 -2.54)
+        (stroke (width 0) (type default))
+        (fill (type background))
+      )
+      (text "Bank 0" (at 11.43 5.08 0)
+        (effects (font (size 2.54 2.54)))
+      )
+      (pin bidirectional line (at 0 0 0) (length 3.81)
+        (name "DONE_0" (effects (font (size 1.27 1.27))))
+        (number "AD8" (effects (font (size 1.27 1.27))))
+      )
+    )
+  )
+)
+"#;
+        assert_eq!(write_kicad_sym(&[symbol]), expected);
+    }
+}
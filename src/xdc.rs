@@ -0,0 +1,452 @@
+use std::collections::{BTreeMap, HashMap};
+
+use regex::Regex;
+
+use crate::{
+    pinout::{IoType, Pin, PinoutTable},
+    sort::natural_cmp,
+};
+
+///Whether the pin is a user I/O that can be placed with `PACKAGE_PIN`
+pub fn is_user_io(pin: &Pin) -> bool {
+    match pin.io_type {
+        IoType::Hp | IoType::Hd | IoType::Hr => pin.name.starts_with("IO_"),
+        IoType::PsMio => pin.name.starts_with("PS_MIO"),
+        _ => false,
+    }
+}
+
+///Default IOSTANDARD for the bank type of a user I/O
+pub fn default_iostandard(io_type: &IoType) -> Option<&'static str> {
+    match io_type {
+        IoType::Hp => Some("LVCMOS18"),
+        IoType::Hd | IoType::Hr => Some("LVCMOS33"),
+        IoType::PsMio => Some("LVCMOS18"),
+        _ => None,
+    }
+}
+
+///IOSTANDARD overrides per bank type, e.g. `HP` -> `LVDS`
+pub type IoStandards = BTreeMap<String, String>;
+
+///User I/O pins by bank, in natural name order
+fn user_io_by_bank(table: &PinoutTable) -> BTreeMap<u32, Vec<&Pin>> {
+    let mut banks: BTreeMap<u32, Vec<&Pin>> = BTreeMap::new();
+    for pin in table.pins.iter().filter(|pin| is_user_io(pin)) {
+        banks.entry(pin.bank.unwrap_or(0)).or_default().push(pin);
+    }
+    for pins in banks.values_mut() {
+        pins.sort_by(|a, b| natural_cmp(&a.name, &b.name));
+    }
+    banks
+}
+
+fn push_header(xdc: &mut String, table: &PinoutTable) {
+    if let Some(device) = &table.info.device {
+        xdc.push_str(&format!("# Xilinx {}", device));
+        if let Some(revision) = &table.info.revision {
+            xdc.push_str(&format!(", pinout revision {}", revision));
+        }
+        xdc.push('\n');
+    }
+}
+
+///Render a commented XDC skeleton with a `PACKAGE_PIN` and `IOSTANDARD`
+///template for every user I/O, grouped by bank
+pub fn write_template(table: &PinoutTable, overrides: &IoStandards) -> String {
+    let mut xdc = String::new();
+    push_header(&mut xdc, table);
+    xdc.push_str("# Uncomment the pins in use and replace the port names.\n");
+
+    for (bank, pins) in user_io_by_bank(table) {
+        let io_type = &pins[0].io_type;
+        let iostandard = overrides
+            .get(io_type.as_str())
+            .map(|s| s.as_str())
+            .or(default_iostandard(io_type))
+            .unwrap_or("<IOSTANDARD>");
+
+        xdc.push_str(&format!(
+            "\n# Bank {} ({}), default IOSTANDARD {}\n",
+            bank, io_type, iostandard
+        ));
+        if *io_type == IoType::PsMio {
+            xdc.push_str("# PS MIO is normally configured in the processing system IP\n");
+        }
+        for pin in pins {
+            xdc.push_str(&format!(
+                "# set_property PACKAGE_PIN {} [get_ports {{{}}}]\n",
+                pin.number, pin.name
+            ));
+            xdc.push_str(&format!(
+                "# set_property IOSTANDARD {} [get_ports {{{}}}]\n",
+                iostandard, pin.name
+            ));
+        }
+    }
+
+    xdc
+}
+
+///Port name for a KiCad net, the hierarchical sheet path is dropped.
+///Auto-named nets (`Net-(U1-PadA1)`, `unconnected-(...)`) have none.
+pub fn port_name(net: &str) -> Option<&str> {
+    if net.starts_with("Net-(") || net.starts_with("unconnected-(") {
+        return None;
+    }
+    let name = net.rsplit('/').next().unwrap_or(net);
+    (!name.is_empty()).then_some(name)
+}
+
+///Port name of every named net in `nets`. The sheet path is dropped unless
+///two nets would end up with the same name, those keep it with `/` turned
+///into `_` (`/a/LED0` becomes `a_LED0`).
+fn port_names<'a>(nets: impl Iterator<Item = &'a str>) -> HashMap<&'a str, String> {
+    let mut by_name: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for net in nets {
+        if let Some(name) = port_name(net) {
+            let nets = by_name.entry(name).or_default();
+            if !nets.contains(&net) {
+                nets.push(net);
+            }
+        }
+    }
+
+    let mut ports = HashMap::new();
+    for (name, nets) in by_name {
+        for &net in &nets {
+            let port = if nets.len() == 1 {
+                name.to_string()
+            } else {
+                net.trim_start_matches('/').replace('/', "_")
+            };
+            ports.insert(net, port);
+        }
+    }
+    ports
+}
+
+///Render `PACKAGE_PIN` constraints for the user I/O that carry a named net,
+///`nets` maps ball numbers to net names. A port gets one ball, further balls
+///on the same net are listed as comments. Returns the constraints and the
+///number of assigned pins.
+pub fn write_assignments(table: &PinoutTable, nets: &BTreeMap<String, String>) -> (String, usize) {
+    let mut xdc = String::new();
+    push_header(&mut xdc, table);
+    xdc.push_str("# Generated from the board netlist.\n");
+
+    let banks = user_io_by_bank(table);
+    let ports = port_names(
+        banks
+            .values()
+            .flatten()
+            .filter_map(|pin| nets.get(&pin.number))
+            .map(|net| net.as_str()),
+    );
+    let mut written: HashMap<&str, &str> = HashMap::new();
+    let mut assigned = 0;
+    for (bank, pins) in banks {
+        let pins: Vec<(&Pin, &String)> = pins
+            .into_iter()
+            .filter_map(|pin| Some((pin, nets.get(&pin.number)?)))
+            .collect();
+        if pins.is_empty() {
+            continue;
+        }
+
+        xdc.push_str(&format!("\n# Bank {} ({})\n", bank, pins[0].0.io_type));
+        for (pin, net) in pins {
+            let Some(port) = ports.get(net.as_str()) else {
+                // 未命名网络只留注释，避免生成无意义的端口名
+                xdc.push_str(&format!(
+                    "# {} ({}) is on the unnamed net {}\n",
+                    pin.number, pin.name, net
+                ));
+                continue;
+            };
+            if let Some(first) = written.get(port.as_str()) {
+                // 一个端口只能对应一个焊球
+                xdc.push_str(&format!(
+                    "# {} ({}) is also on {}, already placed on {}\n",
+                    pin.number, pin.name, port, first
+                ));
+                continue;
+            }
+            written.insert(port, &pin.number);
+            xdc.push_str(&format!(
+                "set_property PACKAGE_PIN {} [get_ports {{{}}}]\n",
+                pin.number, port
+            ));
+            assigned += 1;
+        }
+    }
+
+    (xdc, assigned)
+}
+
+///Pin placement read from one `set_property` line of an XDC file
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Constraint {
+    ///1-based line number in the XDC file
+    pub line: usize,
+    pub port: String,
+    pub package_pin: Option<String>,
+    pub iostandard: Option<String>,
+}
+
+///Collect `PACKAGE_PIN` and `IOSTANDARD` properties, both the plain
+///`set_property NAME VALUE [get_ports ...]` and the `-dict {...}` form.
+///Commented lines are ignored.
+pub fn parse_constraints(text: &str) -> Vec<Constraint> {
+    let re_set = Regex::new(
+        r#"^\s*set_property\s+(?:-dict\s+\{([^}]*)\}|(\S+)\s+(\S+))\s+\[\s*get_ports\s+(?:\{([^}]*)\}|"([^"]*)"|([^\]\s]+))\s*\]"#,
+    )
+    .unwrap();
+
+    let mut constraints = Vec::new();
+    for (line_num, line) in text.lines().enumerate() {
+        let Some(caps) = re_set.captures(line) else {
+            continue;
+        };
+        let port = (4..=6)
+            .find_map(|i| caps.get(i))
+            .map(|m| m.as_str().trim().to_string())
+            .unwrap_or_default();
+
+        let properties: Vec<(&str, &str)> = match caps.get(1) {
+            Some(dict) => {
+                let items: Vec<&str> = dict.as_str().split_whitespace().collect();
+                items
+                    .chunks(2)
+                    .filter_map(|kv| Some((kv[0], *kv.get(1)?)))
+                    .collect()
+            }
+            None => vec![(caps.get(2).unwrap().as_str(), caps.get(3).unwrap().as_str())],
+        };
+
+        let mut constraint = Constraint {
+            line: line_num + 1,
+            port,
+            ..Default::default()
+        };
+        for (key, value) in properties {
+            match key.to_ascii_uppercase().as_str() {
+                "PACKAGE_PIN" => constraint.package_pin = Some(value.to_ascii_uppercase()),
+                "IOSTANDARD" => constraint.iostandard = Some(value.to_ascii_uppercase()),
+                _ => {}
+            }
+        }
+        if constraint.package_pin.is_some() || constraint.iostandard.is_some() {
+            constraints.push(constraint);
+        }
+    }
+    constraints
+}
+
+///Whether a bank of `io_type` can use `iostandard`. HP banks have no 2.5 V
+///or 3.3 V standards, HD and HR banks have no DCI and no 1.8 V LVDS, PS MIO
+///only takes LVCMOS.
+pub fn fits_bank(io_type: &IoType, iostandard: &str) -> bool {
+    let std = iostandard.to_ascii_uppercase();
+    let high_voltage =
+        std == "LVTTL" || std.ends_with("25") || std.ends_with("33") || std == "LVPECL";
+    match io_type {
+        IoType::Hp => !high_voltage,
+        IoType::Hd | IoType::Hr => {
+            !(std == "LVDS"
+                || std == "SUB_LVDS"
+                || std.starts_with("SLVS")
+                || std.starts_with("MIPI")
+                || std.starts_with("POD")
+                || std.starts_with("DIFF_POD")
+                || std.contains("DCI"))
+        }
+        IoType::PsMio => matches!(std.as_str(), "LVCMOS18" | "LVCMOS25" | "LVCMOS33"),
+        _ => true,
+    }
+}
+
+///A problem found by [`check`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Issue {
+    ///1-based line number in the XDC file
+    pub line: usize,
+    pub message: String,
+}
+
+///Check XDC constraints against the pinout: unknown balls, balls that are
+///not user I/O, balls or ports assigned twice and IOSTANDARDs the bank type
+///does not support. Issues are sorted by line.
+pub fn check(table: &PinoutTable, constraints: &[Constraint]) -> Vec<Issue> {
+    let pins: HashMap<&str, &Pin> = table.pins.iter().map(|p| (p.number.as_str(), p)).collect();
+    let mut issues = Vec::new();
+    let mut issue = |line: usize, message: String| issues.push(Issue { line, message });
+
+    // 端口 -> (焊球, 行号)，用于之后检查 IOSTANDARD
+    let mut port_balls: HashMap<&str, (&str, usize)> = HashMap::new();
+    let mut ball_ports: HashMap<&str, (&str, usize)> = HashMap::new();
+    for c in constraints {
+        let Some(ball) = c.package_pin.as_deref() else {
+            continue;
+        };
+        match pins.get(ball) {
+            None => issue(
+                c.line,
+                format!("ball {} does not exist in this package", ball),
+            ),
+            Some(pin) if !is_user_io(pin) => issue(
+                c.line,
+                format!(
+                    "ball {} is {} ({}), not a user I/O",
+                    ball, pin.name, pin.io_type
+                ),
+            ),
+            Some(_) => {}
+        }
+
+        if let Some(&(other, line)) = ball_ports.get(ball) {
+            if other != c.port {
+                issue(
+                    c.line,
+                    format!(
+                        "ball {} is already assigned to {} on line {}",
+                        ball, other, line
+                    ),
+                );
+            }
+        } else {
+            ball_ports.insert(ball, (&c.port, c.line));
+        }
+        if let Some(&(other, line)) = port_balls.get(c.port.as_str()) {
+            if other != ball {
+                issue(
+                    c.line,
+                    format!(
+                        "port {} is already placed on {} on line {}",
+                        c.port, other, line
+                    ),
+                );
+            }
+        } else {
+            port_balls.insert(&c.port, (ball, c.line));
+        }
+    }
+
+    for c in constraints {
+        let Some(iostandard) = c.iostandard.as_deref() else {
+            continue;
+        };
+        let Some(pin) = port_balls
+            .get(c.port.as_str())
+            .and_then(|(ball, _)| pins.get(ball))
+        else {
+            continue;
+        };
+        if is_user_io(pin) && !fits_bank(&pin.io_type, iostandard) {
+            issue(
+                c.line,
+                format!(
+                    "IOSTANDARD {} on {} does not fit bank {} ({})",
+                    iostandard,
+                    c.port,
+                    pin.bank.map_or("?".to_string(), |b| b.to_string()),
+                    pin.io_type
+                ),
+            );
+        }
+    }
+
+    issues.sort_by_key(|i| i.line);
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pin(number: &str, name: &str, bank: u32, io_type: IoType) -> Pin {
+        Pin::test(number, name, Some(bank), io_type)
+    }
+
+    #[test]
+    fn port_names_keep_the_sheet_path_on_collisions() {
+        assert_eq!(port_name("/fpga/LED0"), Some("LED0"));
+        assert_eq!(port_name("CLK"), Some("CLK"));
+        assert_eq!(port_name("Net-(U1-PadA1)"), None);
+        assert_eq!(port_name("unconnected-(U1-PadA2)"), None);
+        assert_eq!(port_name("/fpga/"), None);
+
+        let ports = port_names(["/a/LED0", "/b/LED0", "/CLK", "/CLK"].into_iter());
+        assert_eq!(ports["/a/LED0"], "a_LED0");
+        assert_eq!(ports["/b/LED0"], "b_LED0");
+        assert_eq!(ports["/CLK"], "CLK");
+    }
+
+    #[test]
+    fn writes_one_ball_per_port() {
+        let table = PinoutTable::test(vec![
+            pin("AN14", "IO_L1P_AD11P_44", 44, IoType::Hd),
+            pin("AP14", "IO_L1N_AD11N_44", 44, IoType::Hd),
+            pin("AM14", "IO_L2P_AD10P_44", 44, IoType::Hd),
+            pin("AN13", "IO_L2N_AD10N_44", 44, IoType::Hd),
+            pin("AL13", "IO_L3P_AD9P_44", 44, IoType::Hd),
+            pin("A1", "GND", 0, IoType::Na),
+        ]);
+        let nets: BTreeMap<String, String> = [
+            ("AN14", "/a/LED0"),
+            ("AP14", "/b/LED0"),
+            ("AM14", "/CLK"),
+            ("AN13", "/CLK"),
+            ("AL13", "Net-(U1-PadAL13)"),
+            ("A1", "GND"),
+        ]
+        .into_iter()
+        .map(|(ball, net)| (ball.to_string(), net.to_string()))
+        .collect();
+
+        let (xdc, assigned) = write_assignments(&table, &nets);
+        assert_eq!(assigned, 3);
+        assert!(xdc.contains("set_property PACKAGE_PIN AN14 [get_ports {a_LED0}]\n"));
+        assert!(xdc.contains("set_property PACKAGE_PIN AP14 [get_ports {b_LED0}]\n"));
+        assert!(xdc.contains("set_property PACKAGE_PIN AN13 [get_ports {CLK}]\n"));
+        assert!(xdc.contains("# AM14 (IO_L2P_AD10P_44) is also on CLK, already placed on AN13\n"));
+        assert!(xdc.contains("# AL13 (IO_L3P_AD9P_44) is on the unnamed net Net-(U1-PadAL13)\n"));
+        assert!(!xdc.contains("GND"));
+
+        // 生成的约束必须能通过 check-xdc
+        assert!(check(&table, &parse_constraints(&xdc)).is_empty());
+    }
+
+    #[test]
+    fn flags_bad_constraints() {
+        let table = PinoutTable::test(vec![
+            pin("AN14", "IO_L1P_AD11P_44", 44, IoType::Hd),
+            pin("AP14", "IO_L1N_AD11N_44", 44, IoType::Hd),
+            pin("A1", "GND", 0, IoType::Na),
+        ]);
+        let xdc = "\
+# set_property PACKAGE_PIN ZZ9 [get_ports {commented}]
+set_property PACKAGE_PIN AN14 [get_ports {led[0]}]
+set_property IOSTANDARD LVDS [get_ports {led[0]}]
+set_property -dict {PACKAGE_PIN AN14 IOSTANDARD LVCMOS33} [get_ports btn]
+set_property PACKAGE_PIN A1 [get_ports gnd]
+set_property PACKAGE_PIN ZZ9 [get_ports missing]
+";
+        let constraints = parse_constraints(xdc);
+        assert_eq!(constraints.len(), 5);
+        assert_eq!(constraints[2].port, "btn");
+        assert_eq!(constraints[2].iostandard.as_deref(), Some("LVCMOS33"));
+
+        let lines: Vec<usize> = check(&table, &constraints).iter().map(|i| i.line).collect();
+        assert_eq!(lines, vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn iostandards_by_bank_type() {
+        assert!(fits_bank(&IoType::Hp, "LVDS"));
+        assert!(!fits_bank(&IoType::Hp, "LVCMOS33"));
+        assert!(!fits_bank(&IoType::Hd, "LVDS"));
+        assert!(fits_bank(&IoType::Hd, "LVDS_25"));
+        assert!(!fits_bank(&IoType::PsMio, "SSTL12"));
+    }
+}